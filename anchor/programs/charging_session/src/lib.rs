@@ -1,11 +1,103 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::pubkey;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::sysvar::instructions as sysvar_instructions;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::spl_token_2022::extension::ExtensionType;
+use anchor_spl::token_interface::{
+    self, Burn, InitializeMint2, Mint, MintTo, NonTransferableMintInitialize, PermanentDelegateInitialize,
+    Token2022, TokenAccount,
+};
+use points_marketplace::cpi::accounts::MarkVoucherRedeemed;
+use points_marketplace::program::PointsMarketplace;
+use points_marketplace::{PointsVoucher, VOUCHER_REDEEMER_SEED};
 
 declare_id!("5emVuARWebNveyqe9ivrM24yhBMdLWJvq3qzYTDDd66u");
 
-// Authorized program IDs for CPI calls
-pub const MARKETPLACE_PROGRAM_ID: Pubkey = pubkey!("9PQHr2B1MoxNwyjwdvxZcc7VifqKsetsjvikGwxu2Eko");
-pub const VIRTUAL_PLOT_PROGRAM_ID: Pubkey = pubkey!("Ex4pz9FX9RQUHcSdb74MzTN4hpPFAHMKfqf3RtWcVHRc");
+// Longest seed an authorized caller's authority PDA may be derived from
+// (must match the max_len attribute on AuthorizedCaller)
+pub const MAX_CALLER_SEED_LEN: usize = 32;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Singleton protocol configuration PDA
+pub const PROTOCOL_CONFIG_SEED: &[u8] = b"protocol_config";
+
+// Point multipliers in basis points (10_000 = 1x). Multipliers never go below 1x, so a
+// driver gains nothing by leaving the schedule out of update_session. Demand-response events
+// are read from a fixed per-region slot, so they cannot be left out or borrowed from another region
+pub const MULTIPLIER_BPS_BASE: u16 = 10_000;
+pub const MAX_MULTIPLIER_BPS: u16 = 50_000;
+pub const MAX_TOU_WINDOWS: usize = 8; // must match the max_len attribute on MultiplierSchedule
+pub const MAX_DEMAND_RESPONSE_SECS: i64 = 24 * 3600;
+
+// Upper bound on a published grid carbon intensity (gCO2/kWh); even coal-heavy grids stay below it
+pub const MAX_CARBON_INTENSITY_G_PER_KWH: u32 = 2_000;
+
+// Loyalty tiers above the base level (must match the max_len attribute on ProtocolConfig)
+pub const MAX_LOYALTY_TIERS: usize = 4;
+pub const MAX_LOYALTY_DISCOUNT_BPS: u16 = 5_000;
+
+// Achievements (bit flags on UserAccount::achievements), mirroring the Pulse badges
+pub const ACHIEVEMENT_FIRST_SESSION: u32 = 1 << 0;
+pub const ACHIEVEMENT_100_KWH: u32 = 1 << 1;
+pub const ACHIEVEMENT_7_DAY_STREAK: u32 = 1 << 2;
+pub const ACHIEVEMENT_FAST_CHARGER: u32 = 1 << 3;
+pub const ACHIEVEMENT_NIGHT_OWL: u32 = 1 << 4;
+// One-time bonus points for each achievement, indexed by its bit position
+pub const ACHIEVEMENT_BONUS_POINTS: [u64; 5] = [10, 100, 50, 20, 20];
+pub const FAST_CHARGER_MIN_KW: u16 = 22;
+pub const NIGHT_OWL_END_HOUR: i64 = 5; // sessions ending from midnight until this hour (UTC)
+
+// Streak milestones (must match the max_len attribute on ProtocolConfig) and banked grace days
+pub const MAX_STREAK_MILESTONES: usize = 4;
+pub const MAX_GRACE_DAYS: u8 = 3;
+
+// Largest share of a referee's session points that can be paid to their referrer
+pub const MAX_REFERRAL_REWARD_PCT: u8 = 50;
+
+// Dated point lots tracked per user for expiry (must match the max_len attribute on UserAccount)
+pub const MAX_POINT_LOTS: usize = 12;
+
+pub const MAX_TRANSFER_MEMO_LEN: usize = 64;
+
+// Fleet limits (must match the max_len attributes on Fleet)
+pub const MAX_FLEET_NAME_LEN: usize = 32;
+pub const MAX_FLEET_ADMINS: usize = 5;
+pub const FLEET_SEED: &[u8] = b"fleet";
+
+// Season leaderboard size (must match the max_len attributes on Season and ProtocolConfig)
+pub const MAX_SEASON_ENTRIES: usize = 10;
+pub const SEASON_SEED: &[u8] = b"season";
+
+// Points are a Token-2022 mint; this PDA is its mint authority and permanent delegate
+pub const POINTS_MINT_SEED: &[u8] = b"points_mint";
+pub const POINTS_AUTHORITY_SEED: &[u8] = b"points_authority";
+
+// Number of finished sessions kept in each user's history ring buffer
+pub const SESSION_HISTORY_LEN: usize = 32;
+
+// Session delegate permissions (bit flags)
+pub const DELEGATE_SCOPE_UPDATE: u8 = 1 << 0;
+pub const DELEGATE_SCOPE_END: u8 = 1 << 1;
+
+// Share of an expired session's rent paid to whoever cranks expire_session
+pub const EXPIRY_BOUNTY_PCT: u64 = 10;
+
+// Charger station limits (must match the max_len attributes on ChargerStation)
+pub const MAX_CHARGER_CODE_LEN: usize = 20;
+pub const MAX_CONNECTOR_TYPES: usize = 6;
+
+// Upper bound on how far a station may relax the energy plausibility check
+pub const MAX_ENERGY_TOLERANCE_PCT: u8 = 50;
+
+// Meter reading message: session PDA (32) + reading sequence (8) + cumulative imported Wh (8)
+// + cumulative exported Wh (8)
+pub const METER_READING_MESSAGE_LEN: usize = 56;
+
+// Ed25519 precompile instruction layout: num_signatures (1) + padding (1) + offsets (14)
+const ED25519_OFFSETS_START: usize = 2;
+const ED25519_OFFSETS_SIZE: usize = 14;
 
 #[program]
 pub mod charging_session {
@@ -13,356 +105,5378 @@ pub mod charging_session {
 
     /// Initialize a new charging session
     /// Uses timestamp + nonce to prevent PDA collisions if multiple sessions start in same second
+    /// The timestamp only seeds the session PDA; the start time is taken from the cluster clock so
+    /// a driver cannot push out the session's duration limits by claiming a later start
+    /// Charger power, pricing and idle fees are copied from the registered station, not supplied by the driver
+    /// An optional lamport deposit is locked in the session escrow PDA and settled at end_session
+    /// Energy cost and idle fee are only collected from the deposit, and never beyond it; sessions
+    /// without a deposit record the amount due (including the idle fee) for the operator to bill off-chain
+    /// The points rate and max session duration are fixed from the protocol config at start
+    /// Discharge (vehicle-to-grid) sessions are only allowed on stations that support V2G
     pub fn start_session(
         ctx: Context<StartSession>,
-        charger_code: String,
-        charger_power_kw: u16,
-        pricing_per_kwh: u64, // in lamports
         timestamp: i64,
         nonce: u32,
+        deposit_lamports: u64,
+        mode: SessionMode,
     ) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let station = &ctx.accounts.station;
+        let meter_registration = &ctx.accounts.meter_registration;
         let session = &mut ctx.accounts.session;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!config.is_paused, ErrorCode::ProtocolPaused);
+        require!(station.is_active, ErrorCode::StationInactive);
+        require!(
+            mode == SessionMode::Charge || station.supports_v2g,
+            ErrorCode::StationNotV2g
+        );
+        require!(meter_registration.is_active, ErrorCode::MeterInactive);
+        require!(
+            meter_registration.charger_code == station.code,
+            ErrorCode::MeterChargerMismatch
+        );
+        require_keys_eq!(
+            meter_registration.operator,
+            station.operator,
+            ErrorCode::MeterOperatorMismatch
+        );
+        require!(
+            ctx.accounts.escrow.is_some() == (deposit_lamports > 0),
+            ErrorCode::EscrowMismatch
+        );
+        require!(
+            deposit_lamports == 0
+                || (deposit_lamports >= config.min_deposit_lamports
+                    && deposit_lamports <= config.max_deposit_lamports),
+            ErrorCode::DepositOutOfRange
+        );
+
+        if let Some(escrow) = ctx.accounts.escrow.as_mut() {
+            // Transfer deposit from driver to escrow
+            let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+                &ctx.accounts.user.key(),
+                &escrow.key(),
+                deposit_lamports,
+            );
+
+            anchor_lang::solana_program::program::invoke(
+                &transfer_instruction,
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    escrow.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+
+            escrow.session = session.key();
+            escrow.user = ctx.accounts.user.key();
+            escrow.operator = station.operator;
+            escrow.deposit_lamports = deposit_lamports;
+            escrow.bump = ctx.bumps.escrow.ok_or(ErrorCode::EscrowMismatch)?;
+        }
 
         session.user = ctx.accounts.user.key();
-        session.charger_code = charger_code;
-        session.charger_power_kw = charger_power_kw;
-        session.pricing_per_kwh = pricing_per_kwh;
-        session.start_time = timestamp;
+        session.station = station.key();
+        session.charger_code = station.code.clone();
+        session.charger_power_kw = station.charger_power_kw;
+        session.pricing_per_kwh = station.pricing_per_kwh;
+        session.energy_tolerance_pct = station.energy_tolerance_pct;
+        session.mode = mode;
+        session.points_per_kwh = config.points_per_kwh;
+        session.export_points_per_kwh = config.export_points_per_kwh;
+        session.grid_region = station.grid_region;
+        session.ice_baseline_g_co2_per_kwh = config.ice_baseline_g_co2_per_kwh;
+        session.low_carbon_threshold_g_co2_per_kwh = config.low_carbon_threshold_g_co2_per_kwh;
+        session.low_carbon_points_per_kwh = config.low_carbon_points_per_kwh;
+        session.max_duration_secs = station.max_session_duration_secs.min(config.max_session_duration_secs);
+        session.min_duration_secs = config.min_session_duration_secs;
+        session.idle_timeout_secs = station.idle_timeout_secs;
+        session.idle_fee_per_minute_lamports = station.idle_fee_per_minute_lamports;
+        session.idle_grace_period_secs = station.idle_grace_period_secs;
+        session.paused_at = None;
+        session.total_paused_secs = 0;
+        session.idle_fee_lamports = 0;
+        session.start_time = now;
+        session.seed_timestamp = timestamp;
         session.nonce = nonce;
+        session.meter = meter_registration.meter;
+        session.delegate = None;
+        session.delegate_scope = 0;
+        session.delegate_expires_at = 0;
+        session.last_reading_sequence = 0;
+        session.last_update_time = now;
+        session.last_energy_time = now;
+        session.anomaly_count = 0;
+        session.last_anomaly_time = None;
+        session.deposit_lamports = deposit_lamports;
+        session.amount_paid_lamports = 0;
+        session.deposit_exhausted = false;
         session.energy_consumed_wh = 0;
+        session.energy_exported_wh = 0;
+        session.grid_carbon_g_co2_per_kwh = None;
+        session.co2_avoided_g = 0;
         session.points_earned = 0;
+        session.base_milli_points = 0;
+        session.tou_bonus_milli_points = 0;
+        session.dr_bonus_milli_points = 0;
+        session.export_milli_points = 0;
+        session.carbon_bonus_milli_points = 0;
+        session.loyalty_bonus_milli_points = 0;
+        session.achievement_bonus_points = 0;
+        session.streak_bonus_points = 0;
+        session.fleet = ctx.accounts.user_account.fleet;
         session.is_active = true;
         session.bump = ctx.bumps.session;
 
-        msg!("Charging session started for charger: {} (nonce: {})", session.charger_code, nonce);
+        emit!(SessionStarted {
+            session: session.key(),
+            user: session.user,
+            station: session.station,
+            charger_code: session.charger_code.clone(),
+            mode,
+            meter: session.meter,
+            deposit_lamports,
+            points_per_kwh: session.points_per_kwh,
+            start_time: now,
+        });
+
+        msg!("Charging session started for charger: {} (seed: {}/{}, deposit: {} lamports, mode: {:?})",
+             session.charger_code, timestamp, nonce, deposit_lamports, mode);
         Ok(())
     }
 
     /// Update session with energy consumed (called periodically during charging)
+    /// Signed by the driver or by a session delegate with update permission
+    /// SECURITY: The reading must be attested by the session's registered meter through an
+    /// Ed25519 precompile instruction placed immediately before this one in the transaction.
+    /// The signed message binds the session PDA, a strictly increasing reading sequence and
+    /// the cumulative Wh delivered, so readings cannot be inflated, replayed or moved between sessions
+    /// Readings reporting more energy than the charger could deliver since the last accepted
+    /// reading are not applied; they are counted as anomalies on the session instead of failing,
+    /// so the anomaly record persists for fraud review
+    /// Prepaid sessions stop accruing energy and points once the deposit is used up
+    /// Each increment earns points at the time-of-use multiplier active now, further scaled by
+    /// the demand-response event passed in if it is running
+    /// Discharge sessions also report cumulative Wh exported to the grid, which earn points at
+    /// the separate export rate; exports are scaled by demand-response events only, since
+    /// time-of-use windows reward off-peak charging
     pub fn update_session(
         ctx: Context<UpdateSession>,
-        energy_wh_increment: u64,
+        reading_sequence: u64,
+        cumulative_energy_wh: u64,
+        cumulative_exported_wh: u64,
     ) -> Result<()> {
+        let session_key = ctx.accounts.session.key();
         let session = &mut ctx.accounts.session;
 
+        let clock = Clock::get()?;
+
+        require!(!ctx.accounts.config.is_paused, ErrorCode::ProtocolPaused);
         require!(session.is_active, ErrorCode::SessionNotActive);
+        session.check_authority(&ctx.accounts.authority.key(), DELEGATE_SCOPE_UPDATE, clock.unix_timestamp)?;
+        require!(session.paused_at.is_none(), ErrorCode::SessionPaused);
 
-        session.energy_consumed_wh = session.energy_consumed_wh
-            .checked_add(energy_wh_increment)
-            .ok_or(ErrorCode::Overflow)?;
+        let message = meter_reading_message(
+            &session_key,
+            reading_sequence,
+            cumulative_energy_wh,
+            cumulative_exported_wh,
+        );
+        verify_meter_signature(&ctx.accounts.instructions_sysvar, &session.meter, &message)?;
+
+        require!(
+            reading_sequence > session.last_reading_sequence,
+            ErrorCode::StaleMeterReading
+        );
+        require!(
+            cumulative_energy_wh >= session.energy_consumed_wh
+                && cumulative_exported_wh >= session.energy_exported_wh,
+            ErrorCode::EnergyReadingRegressed
+        );
+        require!(
+            session.mode == SessionMode::Discharge || cumulative_exported_wh == 0,
+            ErrorCode::ExportNotAllowed
+        );
 
-        // Calculate points: 1 point per 100 Wh (0.1 kWh)
-        let new_points = energy_wh_increment / 100;
-        session.points_earned = session.points_earned
-            .checked_add(new_points)
+        let energy_wh_increment = cumulative_energy_wh
+            .checked_sub(session.energy_consumed_wh)
+            .ok_or(ErrorCode::Underflow)?;
+        let exported_wh_increment = cumulative_exported_wh
+            .checked_sub(session.energy_exported_wh)
+            .ok_or(ErrorCode::Underflow)?;
+        // Energy moves through the connector in one direction at a time
+        let total_wh_increment = energy_wh_increment
+            .checked_add(exported_wh_increment)
             .ok_or(ErrorCode::Overflow)?;
 
-        msg!("Session updated: {} Wh consumed, {} points earned",
-             session.energy_consumed_wh, session.points_earned);
+        let max_increment_wh = max_plausible_energy_wh(
+            session.charger_power_kw,
+            session.energy_tolerance_pct,
+            clock.unix_timestamp.saturating_sub(session.last_update_time),
+        );
+
+        // Consume the sequence number even for rejected readings so they cannot be replayed later
+        session.last_reading_sequence = reading_sequence;
+        // Any attested energy means the car is still drawing power, even if the reading is not applied
+        if total_wh_increment > 0 {
+            session.last_energy_time = clock.unix_timestamp;
+        }
+
+        if session.deposit_exhausted {
+            msg!("Deposit exhausted: reading ignored, end the session to settle");
+            return Ok(());
+        }
+
+        if total_wh_increment > max_increment_wh {
+            session.anomaly_count = session.anomaly_count
+                .checked_add(1)
+                .ok_or(ErrorCode::Overflow)?;
+            session.last_anomaly_time = Some(clock.unix_timestamp);
+
+            emit!(MeterAnomalyRecorded {
+                session: session_key,
+                reading_sequence,
+                reported_wh: total_wh_increment,
+                max_plausible_wh: max_increment_wh,
+                anomaly_count: session.anomaly_count,
+                timestamp: clock.unix_timestamp,
+            });
+
+            msg!("Implausible reading rejected: {} Wh reported, at most {} Wh possible at {}kW",
+                 total_wh_increment, max_increment_wh, session.charger_power_kw);
+            return Ok(());
+        }
+
+        // Only accrue the energy the deposit can pay for
+        let billable_energy_wh = match session.prepaid_energy_wh() {
+            Some(prepaid_wh) if cumulative_energy_wh >= prepaid_wh => {
+                session.deposit_exhausted = true;
+                prepaid_wh
+            }
+            _ => cumulative_energy_wh,
+        };
+        let accrued_wh = billable_energy_wh
+            .checked_sub(session.energy_consumed_wh)
+            .ok_or(ErrorCode::Underflow)?;
+
+        session.last_update_time = clock.unix_timestamp;
+        session.energy_consumed_wh = billable_energy_wh;
+
+        // Calculate points at the rate fixed when the session started, then apply multipliers
+        let tou_bps = ctx.accounts.schedule.as_ref()
+            .map_or(MULTIPLIER_BPS_BASE, |schedule| schedule.multiplier_at(clock.unix_timestamp));
+        let dr_bps = load_if_exists::<DemandResponseEvent>(&ctx.accounts.demand_response)?
+            .map_or(MULTIPLIER_BPS_BASE, |event| event.multiplier_at(clock.unix_timestamp));
+        session.accrue_points(accrued_wh, tou_bps, dr_bps)?;
+        session.accrue_export_points(exported_wh_increment, dr_bps)?;
+        session.energy_exported_wh = cumulative_exported_wh;
+
+        emit!(SessionUpdated {
+            session: session_key,
+            reading_sequence,
+            energy_consumed_wh: session.energy_consumed_wh,
+            energy_exported_wh: session.energy_exported_wh,
+            points_earned: session.points_earned,
+            tou_multiplier_bps: tou_bps,
+            dr_multiplier_bps: dr_bps,
+            deposit_exhausted: session.deposit_exhausted,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("Session updated: {} Wh consumed, {} Wh exported, {} points earned (multipliers: {} bps time-of-use, {} bps demand response)",
+             session.energy_consumed_wh, session.energy_exported_wh, session.points_earned, tou_bps, dr_bps);
         Ok(())
     }
 
     /// End charging session and mint points to user
+    /// Signed by the driver or by a session delegate with end permission
+    /// Refused until the session reaches the minimum duration set when it started
+    /// Prepaid sessions pay the operator for the energy consumed from escrow and refund the rest
+    /// Avoided emissions and any low-carbon bonus are computed from the station region's
+    /// carbon intensity at the time the session ends, when that region has one published
     pub fn end_session(
         ctx: Context<EndSession>,
     ) -> Result<()> {
+        let carbon_intensity = load_if_exists::<CarbonIntensity>(&ctx.accounts.carbon_intensity)?;
         let session = &mut ctx.accounts.session;
         let user_account = &mut ctx.accounts.user_account;
         let clock = Clock::get()?;
 
         require!(session.is_active, ErrorCode::SessionNotActive);
+        session.check_authority(&ctx.accounts.authority.key(), DELEGATE_SCOPE_END, clock.unix_timestamp)?;
+        require!(
+            clock.unix_timestamp - session.start_time >= session.min_duration_secs as i64,
+            ErrorCode::SessionTooShort
+        );
 
-        session.end_time = Some(clock.unix_timestamp);
-        session.is_active = false;
-
-        // Credit points to user account
-        user_account.total_points = user_account.total_points
-            .checked_add(session.points_earned)
-            .ok_or(ErrorCode::Overflow)?;
-
-        user_account.available_points = user_account.available_points
-            .checked_add(session.points_earned)
-            .ok_or(ErrorCode::Overflow)?;
+        finish_session(
+            session,
+            user_account,
+            ctx.accounts.escrow.as_ref(),
+            ctx.accounts.operator.as_ref(),
+            carbon_intensity.as_ref(),
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
 
-        user_account.total_energy_kwh = user_account.total_energy_kwh
-            .checked_add(session.energy_consumed_wh / 1000)
-            .ok_or(ErrorCode::Overflow)?;
+        record_season_session(
+            session,
+            user_account,
+            ctx.accounts.season.as_deref_mut(),
+            ctx.accounts.next_season.as_deref_mut(),
+            ctx.bumps.next_season,
+            &mut ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
 
-        user_account.total_sessions = user_account.total_sessions
-            .checked_add(1)
-            .ok_or(ErrorCode::Overflow)?;
+        pay_session_points(
+            session,
+            user_account,
+            SessionPointsAccounts {
+                fleet: ctx.accounts.fleet.as_deref_mut(),
+                fleet_member: ctx.accounts.fleet_member.as_deref_mut(),
+                fleet_points_account: ctx.accounts.fleet_points_account.as_deref(),
+                referrer_account: ctx.accounts.referrer_account.as_deref_mut(),
+                referrer_points_account: ctx.accounts.referrer_points_account.as_deref(),
+                user_points_account: &ctx.accounts.user_points_account,
+                points_mint: &ctx.accounts.points_mint,
+                points_authority: &ctx.accounts.points_authority,
+                points_authority_bump: ctx.bumps.points_authority,
+                token_program: &ctx.accounts.token_program,
+            },
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
 
         let duration = session.end_time.unwrap() - session.start_time;
 
-        msg!("Session ended: {} Wh in {} seconds, {} points minted",
-             session.energy_consumed_wh, duration, session.points_earned);
+        emit!(SessionEnded {
+            session: session.key(),
+            user: session.user,
+            energy_consumed_wh: session.energy_consumed_wh,
+            energy_exported_wh: session.energy_exported_wh,
+            points_earned: session.points_earned,
+            amount_paid_lamports: session.amount_paid_lamports,
+            idle_fee_lamports: session.idle_fee_lamports,
+            co2_avoided_g: session.co2_avoided_g,
+            loyalty_tier: user_account.loyalty_tier,
+            achievement_bonus_points: session.achievement_bonus_points,
+            streak_bonus_points: session.streak_bonus_points,
+            end_time: clock.unix_timestamp,
+            expired_by: None,
+        });
+
+        msg!("Session ended: {} Wh in {} seconds, {} points minted, {} gCO2 avoided",
+             session.energy_consumed_wh, duration, session.points_to_mint()?, session.co2_avoided_g);
         Ok(())
     }
 
-    /// Initialize user account
+    /// Initialize user account and its points token account
+    /// An optional referrer account records who referred the driver. Referrals can only be set
+    /// here and the referrer must already exist, so referral links can never form a cycle
     pub fn initialize_user(ctx: Context<InitializeUser>) -> Result<()> {
         let user_account = &mut ctx.accounts.user_account;
 
+        user_account.referrer = None;
+        if let Some(referrer_account) = ctx.accounts.referrer_account.as_mut() {
+            require_keys_neq!(
+                referrer_account.authority,
+                ctx.accounts.authority.key(),
+                ErrorCode::SelfReferral
+            );
+
+            referrer_account.referral_count = referrer_account.referral_count
+                .checked_add(1)
+                .ok_or(ErrorCode::Overflow)?;
+            user_account.referrer = Some(referrer_account.authority);
+
+            msg!("Referred by {}", referrer_account.authority);
+        }
+
         user_account.authority = ctx.accounts.authority.key();
         user_account.total_points = 0;
         user_account.available_points = 0;
         user_account.total_energy_kwh = 0;
+        user_account.energy_remainder_wh = 0;
+        user_account.total_exported_wh = 0;
+        user_account.total_co2_avoided_g = 0;
+        user_account.loyalty_tier = 0;
+        user_account.achievements = 0;
+        user_account.current_streak_days = 0;
+        user_account.longest_streak_days = 0;
+        user_account.last_session_day = 0;
+        user_account.grace_days = 0;
+        user_account.referral_sessions = 0;
+        user_account.referral_energy_wh = 0;
+        user_account.referral_points_paid = 0;
+        user_account.referral_count = 0;
+        user_account.referral_points_earned = 0;
+        user_account.point_lots = Vec::new();
+        user_account.expired_points = 0;
+        user_account.transferred_today = 0;
+        user_account.transfer_day = 0;
+        user_account.fleet = None;
+        user_account.season_id = 0;
+        user_account.season_energy_wh = 0;
+        user_account.season_points = 0;
         user_account.total_sessions = 0;
         user_account.bump = ctx.bumps.user_account;
 
+        emit!(UserInitialized {
+            user_account: user_account.key(),
+            authority: user_account.authority,
+            points_account: ctx.accounts.user_points_account.key(),
+        });
+
         msg!("User account initialized");
         Ok(())
     }
 
     /// Credit points to user (callable via CPI by authorized programs like marketplace)
-    /// SECURITY: Only authorized programs can call this to prevent arbitrary point minting
+    /// Mints points tokens to the user's points token account
+    /// SECURITY: The caller_authority signer must be the registered authority PDA of an
+    /// authorized caller program, and credits count against that caller's daily mint cap
     pub fn credit_points(
         ctx: Context<ModifyPoints>,
         amount: u64,
     ) -> Result<()> {
-        // Verify caller is an authorized program by checking the caller_authority
-        // The calling program must pass itself as the caller_authority signer
-        let caller = ctx.accounts.caller_authority.key();
-        require!(
-            caller == MARKETPLACE_PROGRAM_ID || caller == VIRTUAL_PLOT_PROGRAM_ID,
-            ErrorCode::UnauthorizedCaller
-        );
+        let caller = ctx.accounts.authorized_caller.program_id;
+
+        let now = Clock::get()?.unix_timestamp;
+
+        ctx.accounts.authorized_caller.record_mint(amount, now)?;
 
         let user_account = &mut ctx.accounts.user_account;
 
         user_account.total_points = user_account.total_points
             .checked_add(amount)
             .ok_or(ErrorCode::Overflow)?;
+        user_account.credit_lot(amount, now, ctx.accounts.config.point_lifetime_secs)?;
 
-        user_account.available_points = user_account.available_points
-            .checked_add(amount)
-            .ok_or(ErrorCode::Overflow)?;
+        mint_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            amount,
+        )?;
+
+        emit!(PointsCredited {
+            user_account: ctx.accounts.user_account.key(),
+            authority: ctx.accounts.user_account.authority,
+            amount,
+            caller,
+        });
 
         msg!("Credited {} points to user via CPI from authorized program {}", amount, caller);
         Ok(())
     }
 
     /// Debit points from user (callable via CPI by authorized programs like marketplace)
-    /// SECURITY: Only authorized programs can call this to prevent unauthorized point burning
+    /// Burns points tokens using the points authority's permanent delegate rights
+    /// SECURITY: The caller_authority signer must be the registered authority PDA of an
+    /// authorized caller program
     pub fn debit_points(
         ctx: Context<ModifyPoints>,
         amount: u64,
     ) -> Result<()> {
-        // Verify caller is an authorized program by checking the caller_authority
-        // The calling program must pass itself as the caller_authority signer
-        let caller = ctx.accounts.caller_authority.key();
-        require!(
-            caller == MARKETPLACE_PROGRAM_ID || caller == VIRTUAL_PLOT_PROGRAM_ID,
-            ErrorCode::UnauthorizedCaller
-        );
-
-        let user_account = &mut ctx.accounts.user_account;
+        let caller = ctx.accounts.authorized_caller.program_id;
 
         require!(
-            user_account.available_points >= amount,
+            ctx.accounts.user_points_account.amount >= amount,
             ErrorCode::InsufficientPoints
         );
 
-        user_account.available_points = user_account.available_points
-            .checked_sub(amount)
-            .ok_or(ErrorCode::Underflow)?;
+        burn_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            amount,
+        )?;
+
+        ctx.accounts.user_account.debit_lots(amount);
+
+        emit!(PointsDebited {
+            user_account: ctx.accounts.user_account.key(),
+            authority: ctx.accounts.user_account.authority,
+            amount,
+            caller,
+        });
 
         msg!("Debited {} points from user via CPI from authorized program {}", amount, caller);
         Ok(())
     }
 
     /// Redeem a voucher from the marketplace
-    /// Creates a redemption record to prevent double-spending
-    /// SECURITY: Uses init constraint on redemption_record to prevent double redemption
-    /// The init will fail if a redemption record already exists for this voucher
+    /// The voucher is flipped to redeemed on the marketplace side through a CPI signed by this
+    /// program's voucher redeemer PDA, in the same transaction that mints the points
+    /// SECURITY: The voucher's is_redeemed flag, owned by the marketplace, prevents double
+    /// redemption; the redemption record is the receipt kept on this side. Vouchers redeemed
+    /// before the flag was set here only have the record, so an existing record is refused too
     pub fn redeem_voucher(ctx: Context<RedeemVoucher>) -> Result<()> {
-        let voucher_data = &ctx.accounts.voucher.try_borrow_data()?;
-        let user_account = &mut ctx.accounts.user_account;
-        let redemption_record = &mut ctx.accounts.redemption_record;
+        // Deserialize through the marketplace's account type, which checks the discriminator
+        let voucher = PointsVoucher::try_deserialize(&mut &ctx.accounts.voucher.try_borrow_data()?[..])
+            .map_err(|_| ErrorCode::InvalidVoucherData)?;
+        let points_amount = voucher.points_amount;
+        let now = Clock::get()?.unix_timestamp;
 
-        // Verify voucher is from marketplace program
         require!(
-            ctx.accounts.voucher.owner == &MARKETPLACE_PROGRAM_ID,
-            ErrorCode::InvalidVoucherProgram
+            voucher.buyer == ctx.accounts.user_account.authority,
+            ErrorCode::UnauthorizedVoucher
         );
-
-        // Parse voucher data (skip 8-byte discriminator)
-        // PointsVoucher: buyer (32) + points_amount (8) + is_redeemed (1) + created_at (8) + bump (1)
-        require!(voucher_data.len() >= 58, ErrorCode::InvalidVoucherData);
-
-        let buyer = Pubkey::try_from(&voucher_data[8..40]).map_err(|_| ErrorCode::InvalidVoucherData)?;
-        let points_amount = u64::from_le_bytes(
-            voucher_data[40..48].try_into().map_err(|_| ErrorCode::InvalidVoucherData)?
+        require!(
+            !voucher.is_redeemed && ctx.accounts.redemption_record.redeemed_at == 0,
+            ErrorCode::VoucherAlreadyRedeemed
         );
+        require!(now < voucher.expires_at, ErrorCode::VoucherExpired);
+
+        let signer_seeds: &[&[&[u8]]] = &[&[VOUCHER_REDEEMER_SEED, &[ctx.bumps.voucher_redeemer]]];
+        points_marketplace::cpi::mark_voucher_redeemed(CpiContext::new_with_signer(
+            ctx.accounts.marketplace_program.to_account_info(),
+            MarkVoucherRedeemed {
+                voucher: ctx.accounts.voucher.to_account_info(),
+                caller_authority: ctx.accounts.voucher_redeemer.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
 
-        // Verify voucher belongs to this user
-        require!(buyer == user_account.authority, ErrorCode::UnauthorizedVoucher);
+        let user_account = &mut ctx.accounts.user_account;
+        let redemption_record = &mut ctx.accounts.redemption_record;
 
         // Credit points to user
         user_account.total_points = user_account.total_points
             .checked_add(points_amount)
             .ok_or(ErrorCode::Overflow)?;
+        user_account.credit_lot(points_amount, now, ctx.accounts.config.point_lifetime_secs)?;
 
-        user_account.available_points = user_account.available_points
-            .checked_add(points_amount)
-            .ok_or(ErrorCode::Overflow)?;
+        mint_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            points_amount,
+        )?;
 
-        // Mark redemption record
-        // NOTE: The 'init' constraint on redemption_record prevents double-spending
-        // because it will fail if this PDA already exists
+        // Record the redemption
         redemption_record.voucher = ctx.accounts.voucher.key();
         redemption_record.user = user_account.authority;
         redemption_record.points_amount = points_amount;
-        redemption_record.redeemed_at = Clock::get()?.unix_timestamp;
+        redemption_record.redeemed_at = now;
         redemption_record.bump = ctx.bumps.redemption_record;
 
+        emit!(VoucherRedeemed {
+            voucher: redemption_record.voucher,
+            user_account: user_account.key(),
+            authority: user_account.authority,
+            points_amount,
+            redeemed_at: redemption_record.redeemed_at,
+        });
+
         msg!("Redeemed voucher: {} points credited", points_amount);
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(charger_code: String, charger_power_kw: u16, pricing_per_kwh: u64, timestamp: i64, nonce: u32)]
-pub struct StartSession<'info> {
-    #[account(
-        init,
-        payer = user,
-        space = 8 + ChargingSession::INIT_SPACE,
-        seeds = [b"session", user.key().as_ref(), &timestamp.to_le_bytes(), &nonce.to_le_bytes()],
-        bump
-    )]
-    pub session: Account<'info, ChargingSession>,
+    /// Create the Token-2022 points mint
+    /// The mint is non-transferable, so points only move through this program, which keeps each
+    /// driver's expiry lots in step with their balance; the points authority is permanent delegate
+    /// so points can be burned and re-minted when they move
+    /// SECURITY: Only the program upgrade authority can create the mint
+    pub fn initialize_points_mint(ctx: Context<InitializePointsMint>) -> Result<()> {
+        let points_mint = ctx.accounts.points_mint.to_account_info();
+        let points_authority = ctx.accounts.points_authority.key();
+        let token_program = ctx.accounts.token_program.to_account_info();
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+        let space = token_interface::find_mint_account_size(Some(&vec![
+            ExtensionType::PermanentDelegate,
+            ExtensionType::NonTransferable,
+        ]))?;
+        let mint_seeds: &[&[&[u8]]] = &[&[POINTS_MINT_SEED, &[ctx.bumps.points_mint]]];
+        anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::CreateAccount {
+                    from: ctx.accounts.admin.to_account_info(),
+                    to: points_mint.clone(),
+                },
+                mint_seeds,
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            token_program.key,
+        )?;
 
-    pub system_program: Program<'info, System>,
-}
+        // Extensions must be initialized before the mint itself
+        token_interface::permanent_delegate_initialize(
+            CpiContext::new(
+                token_program.clone(),
+                PermanentDelegateInitialize {
+                    token_program_id: token_program.clone(),
+                    mint: points_mint.clone(),
+                },
+            ),
+            &points_authority,
+        )?;
+        token_interface::non_transferable_mint_initialize(CpiContext::new(
+            token_program.clone(),
+            NonTransferableMintInitialize {
+                token_program_id: token_program.clone(),
+                mint: points_mint.clone(),
+            },
+        ))?;
+        token_interface::initialize_mint2(
+            CpiContext::new(token_program, InitializeMint2 { mint: points_mint.clone() }),
+            0,
+            &points_authority,
+            None,
+        )?;
 
-#[derive(Accounts)]
-pub struct UpdateSession<'info> {
-    #[account(
-        mut,
-        seeds = [b"session", session.user.as_ref(), &session.start_time.to_le_bytes(), &session.nonce.to_le_bytes()],
-        bump = session.bump,
-        has_one = user
-    )]
-    pub session: Account<'info, ChargingSession>,
+        emit!(PointsMintInitialized {
+            mint: points_mint.key(),
+            authority: points_authority,
+        });
 
-    pub user: Signer<'info>,
-}
+        msg!("Points mint initialized: {}", points_mint.key());
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct EndSession<'info> {
-    #[account(
-        mut,
-        seeds = [b"session", session.user.as_ref(), &session.start_time.to_le_bytes(), &session.nonce.to_le_bytes()],
-        bump = session.bump,
-        has_one = user
-    )]
-    pub session: Account<'info, ChargingSession>,
+    /// One-time conversion of a user's legacy balance into points tokens
+    /// The legacy balance is the part of available_points not backed by point lots
+    pub fn migrate_points(ctx: Context<MigratePoints>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let amount = user_account.available_points.saturating_sub(user_account.tracked_points());
 
-    #[account(
-        mut,
-        seeds = [b"user", user.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+        require!(amount > 0, ErrorCode::NoPointsToMigrate);
 
-    #[account(mut)]
-    pub user: Signer<'info>,
-}
+        // credit_lot adds the points back to available_points as a lot
+        user_account.available_points -= amount;
+        user_account.credit_lot(
+            amount,
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.config.point_lifetime_secs,
+        )?;
 
-#[derive(Accounts)]
-pub struct InitializeUser<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + UserAccount::INIT_SPACE,
-        seeds = [b"user", authority.key().as_ref()],
-        bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+        mint_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            amount,
+        )?;
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        emit!(PointsMigrated {
+            user_account: ctx.accounts.user_account.key(),
+            authority: ctx.accounts.authority.key(),
+            amount,
+        });
 
-    pub system_program: Program<'info, System>,
-}
+        msg!("Migrated {} points to token account {}", amount, ctx.accounts.user_points_account.key());
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct ModifyPoints<'info> {
-    #[account(mut)]
-    pub user_account: Account<'info, UserAccount>,
+    /// Spend points on a grace day, which covers one missed day in the driver's streak
+    /// Grace days are banked up to MAX_GRACE_DAYS and used automatically at end_session
+    pub fn buy_grace_day(ctx: Context<BuyGraceDay>) -> Result<()> {
+        let price = ctx.accounts.config.grace_day_price_points;
+        let user_account = &mut ctx.accounts.user_account;
 
-    /// CHECK: PDA of the calling program - must be marketplace or virtual_plot program
-    /// The calling program signs with this PDA to prove its identity
-    pub caller_authority: Signer<'info>,
-}
+        require!(price > 0, ErrorCode::GraceDaysUnavailable);
+        require!(user_account.grace_days < MAX_GRACE_DAYS, ErrorCode::TooManyGraceDays);
+        require!(
+            ctx.accounts.user_points_account.amount >= price,
+            ErrorCode::InsufficientPoints
+        );
 
-#[derive(Accounts)]
-pub struct RedeemVoucher<'info> {
-    #[account(
-        mut,
-        seeds = [b"user", authority.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+        burn_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            price,
+        )?;
 
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + VoucherRedemption::INIT_SPACE,
-        seeds = [b"redemption", voucher.key().as_ref()],
-        bump
-    )]
-    pub redemption_record: Account<'info, VoucherRedemption>,
+        user_account.grace_days += 1;
+        user_account.debit_lots(price);
 
-    /// CHECK: Voucher account from marketplace, validated in instruction
-    pub voucher: AccountInfo<'info>,
+        emit!(GraceDayPurchased {
+            user: user_account.authority,
+            price_points: price,
+            grace_days: user_account.grace_days,
+        });
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        msg!("Grace day bought for {} points, {} banked", price, user_account.grace_days);
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Send points to another registered driver, up to the protocol's daily transfer limit
+    /// Points move lot by lot, so the recipient's points keep the expiry of the sender's lots they came from
+    pub fn transfer_points(
+        ctx: Context<TransferPoints>,
+        amount: u64,
+        memo: Option<String>,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidTransferAmount);
+        require!(
+            memo.as_ref().map_or(0, String::len) <= MAX_TRANSFER_MEMO_LEN,
+            ErrorCode::TransferMemoTooLong
+        );
+        require!(
+            ctx.accounts.sender_points_account.amount >= amount,
+            ErrorCode::InsufficientPoints
+        );
 
-#[account]
-#[derive(InitSpace)]
-pub struct ChargingSession {
-    pub user: Pubkey,
-    #[max_len(20)]
-    pub charger_code: String,
-    pub charger_power_kw: u16,
-    pub pricing_per_kwh: u64,
-    pub start_time: i64,
-    pub nonce: u32,
-    pub end_time: Option<i64>,
-    pub energy_consumed_wh: u64, // in watt-hours
-    pub points_earned: u64,
-    pub is_active: bool,
-    pub bump: u8,
-}
+        let now = Clock::get()?.unix_timestamp;
+        let limit = ctx.accounts.config.daily_transfer_limit_points;
+        let sender_account = &mut ctx.accounts.sender_account;
 
-#[account]
-#[derive(InitSpace)]
-pub struct UserAccount {
-    pub authority: Pubkey,
-    pub total_points: u64,
-    pub available_points: u64,
-    pub total_energy_kwh: u64,
-    pub total_sessions: u64,
-    pub bump: u8,
-}
+        sender_account.record_transfer(amount, limit, now)?;
 
-#[account]
-#[derive(InitSpace)]
-pub struct VoucherRedemption {
-    pub voucher: Pubkey,
-    pub user: Pubkey,
-    pub points_amount: u64,
-    pub redeemed_at: i64,
-    pub bump: u8,
+        move_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.sender_points_account,
+            &ctx.accounts.recipient_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            amount,
+        )?;
+
+        // Each lot keeps its own expiry; points the sender held outside their lots start a
+        // fresh lifetime rather than never expiring
+        let moved_lots = sender_account.debit_lots(amount);
+        let recipient_account = &mut ctx.accounts.recipient_account;
+        for lot in &moved_lots {
+            recipient_account.add_lot(lot.amount, now, lot.expires_at)?;
+        }
+        recipient_account.credit_lot(
+            amount - lots_total(&moved_lots),
+            now,
+            ctx.accounts.config.point_lifetime_secs,
+        )?;
+
+        emit!(PointsTransferred {
+            from: sender_account.authority,
+            to: ctx.accounts.recipient_account.authority,
+            amount,
+            memo,
+        });
+
+        msg!("Transferred {} points to {}", amount, ctx.accounts.recipient_account.authority);
+        Ok(())
+    }
+
+    /// Register a charging station operated by the signer
+    /// The station code is unique - retired stations keep their code so it cannot be reused
+    pub fn register_station(
+        ctx: Context<RegisterStation>,
+        code: String,
+        params: StationParams,
+    ) -> Result<()> {
+        require!(
+            !code.is_empty() && code.len() <= MAX_CHARGER_CODE_LEN,
+            ErrorCode::InvalidChargerCode
+        );
+        params.validate()?;
+
+        let station = &mut ctx.accounts.station;
+
+        station.operator = ctx.accounts.operator.key();
+        station.code = code;
+        station.is_active = true;
+        station.registered_at = Clock::get()?.unix_timestamp;
+        station.bump = ctx.bumps.station;
+        station.apply(params);
+
+        emit!(StationRegistered {
+            station: station.key(),
+            operator: station.operator,
+            code: station.code.clone(),
+            charger_power_kw: station.charger_power_kw,
+            pricing_per_kwh: station.pricing_per_kwh,
+            grid_region: station.grid_region,
+        });
+
+        msg!("Station {} registered: {}kW at {} lamports/kWh",
+             station.code, station.charger_power_kw, station.pricing_per_kwh);
+        Ok(())
+    }
+
+    /// Update a station's connectors, power, pricing, tolerance and location
+    /// Only affects sessions started after the update
+    pub fn update_station(
+        ctx: Context<UpdateStation>,
+        params: StationParams,
+    ) -> Result<()> {
+        params.validate()?;
+
+        let station = &mut ctx.accounts.station;
+
+        require!(station.is_active, ErrorCode::StationInactive);
+
+        station.apply(params);
+
+        emit!(StationUpdated {
+            station: station.key(),
+            charger_power_kw: station.charger_power_kw,
+            pricing_per_kwh: station.pricing_per_kwh,
+            grid_region: station.grid_region,
+            supports_v2g: station.supports_v2g,
+        });
+
+        msg!("Station {} updated: {}kW at {} lamports/kWh",
+             station.code, station.charger_power_kw, station.pricing_per_kwh);
+        Ok(())
+    }
+
+    /// Retire a station so no new sessions can start on it
+    pub fn retire_station(ctx: Context<UpdateStation>) -> Result<()> {
+        let station = &mut ctx.accounts.station;
+
+        require!(station.is_active, ErrorCode::StationInactive);
+
+        station.is_active = false;
+
+        emit!(StationRetired {
+            station: station.key(),
+            code: station.code.clone(),
+        });
+
+        msg!("Station {} retired", station.code);
+        Ok(())
+    }
+
+    /// Register a charger meter key whose signatures attest session energy readings
+    /// The meter is bound to the operator of its charger as well as the charger code; sessions only
+    /// start when the station registered under that code belongs to the same operator, so
+    /// registering another operator's charger code first does not capture its sessions or payouts
+    /// SECURITY: Only the protocol admin can register meters
+    pub fn register_meter(
+        ctx: Context<RegisterMeter>,
+        meter: Pubkey,
+        charger_code: String,
+        operator: Pubkey,
+    ) -> Result<()> {
+        let meter_registration = &mut ctx.accounts.meter_registration;
+
+        meter_registration.meter = meter;
+        meter_registration.charger_code = charger_code;
+        meter_registration.is_active = true;
+        meter_registration.registered_at = Clock::get()?.unix_timestamp;
+        meter_registration.bump = ctx.bumps.meter_registration;
+        meter_registration.operator = operator;
+
+        emit!(MeterRegistered {
+            meter,
+            charger_code: meter_registration.charger_code.clone(),
+            operator,
+        });
+
+        msg!("Meter {} registered for charger: {} (operator {})",
+             meter, meter_registration.charger_code, operator);
+        Ok(())
+    }
+
+    /// Activate or revoke a registered meter (e.g. when a device key is compromised)
+    /// SECURITY: Only the protocol admin can change meter status
+    pub fn set_meter_status(
+        ctx: Context<SetMeterStatus>,
+        is_active: bool,
+    ) -> Result<()> {
+        let meter_registration = &mut ctx.accounts.meter_registration;
+
+        meter_registration.is_active = is_active;
+
+        emit!(MeterStatusChanged {
+            meter: meter_registration.meter,
+            is_active,
+        });
+
+        msg!("Meter {} active: {}", meter_registration.meter, is_active);
+        Ok(())
+    }
+
+    /// Close a finished session, returning its rent to the driver
+    /// The session is folded into the driver's fixed-size history ring buffer first
+    pub fn close_session(ctx: Context<CloseSession>) -> Result<()> {
+        let session = &ctx.accounts.session;
+        let history = &mut ctx.accounts.history;
+
+        require!(!session.is_active, ErrorCode::SessionStillActive);
+        let end_time = session.end_time.ok_or(ErrorCode::SessionStillActive)?;
+
+        if history.user == Pubkey::default() {
+            history.user = session.user;
+            history.bump = ctx.bumps.history;
+        }
+
+        history.push(SessionRecord {
+            charger_code_hash: hash(session.charger_code.as_bytes()).to_bytes(),
+            energy_wh: session.energy_consumed_wh,
+            points: session.points_earned,
+            start_time: session.start_time,
+            end_time,
+        });
+
+        emit!(SessionClosed {
+            session: session.key(),
+            user: session.user,
+            history_records: history.total_records,
+        });
+
+        msg!("Session closed: {} Wh, {} points archived ({} sessions in history)",
+             session.energy_consumed_wh, session.points_earned, history.total_records);
+        Ok(())
+    }
+
+    /// Permissionless crank that ends an abandoned session after its timeout
+    /// Earned points are credited to the driver as in end_session, the session is archived
+    /// into the driver's history if it exists, and the session account is closed with a
+    /// share of its rent paid to the cranker as a bounty and the rest returned to the driver
+    pub fn expire_session(ctx: Context<ExpireSession>) -> Result<()> {
+        let carbon_intensity = load_if_exists::<CarbonIntensity>(&ctx.accounts.carbon_intensity)?;
+        let session = &mut ctx.accounts.session;
+        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
+
+        require!(session.is_active, ErrorCode::SessionNotActive);
+        require!(session.is_expired(clock.unix_timestamp), ErrorCode::SessionNotExpired);
+
+        finish_session(
+            session,
+            user_account,
+            ctx.accounts.escrow.as_ref(),
+            ctx.accounts.operator.as_ref(),
+            carbon_intensity.as_ref(),
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
+
+        record_season_session(
+            session,
+            user_account,
+            ctx.accounts.season.as_deref_mut(),
+            ctx.accounts.next_season.as_deref_mut(),
+            ctx.bumps.next_season,
+            &mut ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
+
+        pay_session_points(
+            session,
+            user_account,
+            SessionPointsAccounts {
+                fleet: ctx.accounts.fleet.as_deref_mut(),
+                fleet_member: ctx.accounts.fleet_member.as_deref_mut(),
+                fleet_points_account: ctx.accounts.fleet_points_account.as_deref(),
+                referrer_account: ctx.accounts.referrer_account.as_deref_mut(),
+                referrer_points_account: ctx.accounts.referrer_points_account.as_deref(),
+                user_points_account: &ctx.accounts.user_points_account,
+                points_mint: &ctx.accounts.points_mint,
+                points_authority: &ctx.accounts.points_authority,
+                points_authority_bump: ctx.bumps.points_authority,
+                token_program: &ctx.accounts.token_program,
+            },
+            &ctx.accounts.config,
+            clock.unix_timestamp,
+        )?;
+
+        if let Some(history) = ctx.accounts.history.as_mut() {
+            history.push(SessionRecord {
+                charger_code_hash: hash(session.charger_code.as_bytes()).to_bytes(),
+                energy_wh: session.energy_consumed_wh,
+                points: session.points_earned,
+                start_time: session.start_time,
+                end_time: clock.unix_timestamp,
+            });
+        }
+
+        // Pay the cranker from the session rent; the close constraint returns the rest to the driver
+        let session_info = session.to_account_info();
+        let bounty = session_info.lamports() * EXPIRY_BOUNTY_PCT / 100;
+        **session_info.try_borrow_mut_lamports()? -= bounty;
+        **ctx.accounts.cranker.try_borrow_mut_lamports()? += bounty;
+
+        emit!(SessionEnded {
+            session: session.key(),
+            user: session.user,
+            energy_consumed_wh: session.energy_consumed_wh,
+            energy_exported_wh: session.energy_exported_wh,
+            points_earned: session.points_earned,
+            amount_paid_lamports: session.amount_paid_lamports,
+            idle_fee_lamports: session.idle_fee_lamports,
+            co2_avoided_g: session.co2_avoided_g,
+            loyalty_tier: user_account.loyalty_tier,
+            achievement_bonus_points: session.achievement_bonus_points,
+            streak_bonus_points: session.streak_bonus_points,
+            end_time: clock.unix_timestamp,
+            expired_by: Some(ctx.accounts.cranker.key()),
+        });
+
+        msg!("Session expired: {} Wh, {} points minted, {} lamports bounty to {}",
+             session.energy_consumed_wh, session.points_to_mint()?, bounty, ctx.accounts.cranker.key());
+        Ok(())
+    }
+
+    /// Create the singleton protocol config holding the points rate, session limits and pause flag
+    /// SECURITY: Only the program upgrade authority can create the config and becomes its admin
+    pub fn initialize_protocol_config(
+        ctx: Context<InitializeProtocolConfig>,
+        params: ProtocolParams,
+    ) -> Result<()> {
+        params.validate()?;
+
+        let config = &mut ctx.accounts.config;
+
+        config.admin = ctx.accounts.admin.key();
+        config.is_paused = false;
+        config.bump = ctx.bumps.config;
+        config.apply(params.clone());
+
+        emit!(ProtocolConfigUpdated {
+            admin: config.admin,
+            params,
+        });
+
+        msg!("Protocol config initialized: {} points/kWh, admin {}", config.points_per_kwh, config.admin);
+        Ok(())
+    }
+
+    /// Update the points rate and session limits
+    /// Only affects sessions started after the update
+    pub fn update_protocol_config(
+        ctx: Context<UpdateProtocolConfig>,
+        params: ProtocolParams,
+    ) -> Result<()> {
+        params.validate()?;
+
+        let config = &mut ctx.accounts.config;
+
+        config.apply(params.clone());
+
+        emit!(ProtocolConfigUpdated {
+            admin: config.admin,
+            params,
+        });
+
+        msg!("Protocol config updated: {} points/kWh, deposits {}-{} lamports, max session {}s",
+             config.points_per_kwh, config.min_deposit_lamports, config.max_deposit_lamports,
+             config.max_session_duration_secs);
+        Ok(())
+    }
+
+    /// Pause or unpause the protocol
+    /// While paused no sessions can start or accrue energy; active sessions can still be ended
+    pub fn set_protocol_paused(
+        ctx: Context<UpdateProtocolConfig>,
+        is_paused: bool,
+    ) -> Result<()> {
+        ctx.accounts.config.is_paused = is_paused;
+
+        emit!(ProtocolPauseChanged { is_paused });
+
+        msg!("Protocol paused: {}", is_paused);
+        Ok(())
+    }
+
+    /// Hand the admin role to a new key
+    /// The new admin must co-sign so the role cannot be moved to a key nobody controls
+    pub fn set_protocol_admin(ctx: Context<SetProtocolAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        config.admin = ctx.accounts.new_admin.key();
+
+        emit!(ProtocolAdminChanged {
+            previous_admin: ctx.accounts.admin.key(),
+            new_admin: config.admin,
+        });
+
+        msg!("Protocol admin rotated to {}", config.admin);
+        Ok(())
+    }
+
+    /// Replace the loyalty tiers, ordered from lowest to highest
+    /// Drivers move to the highest tier whose thresholds they meet when their next session ends
+    pub fn set_loyalty_tiers(
+        ctx: Context<UpdateProtocolConfig>,
+        tiers: Vec<LoyaltyTier>,
+    ) -> Result<()> {
+        require!(tiers.len() <= MAX_LOYALTY_TIERS, ErrorCode::InvalidLoyaltyTier);
+        for tier in &tiers {
+            tier.validate()?;
+        }
+        for pair in tiers.windows(2) {
+            require!(
+                pair[1].min_energy_kwh >= pair[0].min_energy_kwh
+                    && pair[1].min_sessions >= pair[0].min_sessions,
+                ErrorCode::InvalidLoyaltyTier
+            );
+        }
+
+        let config = &mut ctx.accounts.config;
+
+        config.loyalty_tiers = tiers;
+
+        emit!(LoyaltyTiersUpdated {
+            tiers: config.loyalty_tiers.clone(),
+        });
+
+        msg!("Loyalty tiers set: {} above base", config.loyalty_tiers.len());
+        Ok(())
+    }
+
+    /// Replace the streak milestone bonuses and the price of a grace day
+    /// A grace day price of 0 stops grace day sales
+    pub fn set_streak_rewards(
+        ctx: Context<UpdateProtocolConfig>,
+        milestones: Vec<StreakMilestone>,
+        grace_day_price_points: u64,
+    ) -> Result<()> {
+        require!(milestones.len() <= MAX_STREAK_MILESTONES, ErrorCode::InvalidStreakMilestone);
+        require!(
+            milestones.iter().all(|milestone| milestone.days > 0)
+                && milestones.windows(2).all(|pair| pair[1].days > pair[0].days),
+            ErrorCode::InvalidStreakMilestone
+        );
+
+        let config = &mut ctx.accounts.config;
+
+        config.streak_milestones = milestones;
+        config.grace_day_price_points = grace_day_price_points;
+
+        emit!(StreakRewardsUpdated {
+            milestones: config.streak_milestones.clone(),
+            grace_day_price_points,
+        });
+
+        msg!("Streak rewards set: {} milestones, grace day {} points",
+             config.streak_milestones.len(), grace_day_price_points);
+        Ok(())
+    }
+
+    /// Replace the referral reward terms; a reward of 0% turns referral rewards off
+    /// Only affects sessions ended after the update
+    pub fn set_referral_terms(
+        ctx: Context<UpdateProtocolConfig>,
+        terms: ReferralTerms,
+    ) -> Result<()> {
+        require!(
+            terms.reward_pct <= MAX_REFERRAL_REWARD_PCT,
+            ErrorCode::InvalidReferralTerms
+        );
+
+        let config = &mut ctx.accounts.config;
+
+        config.referral_terms = terms;
+
+        emit!(ReferralTermsUpdated {
+            terms: config.referral_terms.clone(),
+        });
+
+        msg!("Referral terms set: {}% for the first {} sessions or {} kWh, capped at {} points",
+             config.referral_terms.reward_pct, config.referral_terms.max_sessions,
+             config.referral_terms.max_energy_kwh, config.referral_terms.max_points_per_referee);
+        Ok(())
+    }
+
+    /// Report a driver's loyalty tier and its benefits, for other programs to read via CPI
+    pub fn get_loyalty_benefits(ctx: Context<GetLoyaltyBenefits>) -> Result<LoyaltyBenefits> {
+        Ok(ctx.accounts.config.loyalty_benefits(ctx.accounts.user_account.loyalty_tier))
+    }
+
+    /// Replace the time-of-use multiplier schedule
+    /// SECURITY: Only the protocol admin can set the schedule
+    pub fn set_tou_schedule(
+        ctx: Context<SetTouSchedule>,
+        windows: Vec<TouWindow>,
+    ) -> Result<()> {
+        require!(windows.len() <= MAX_TOU_WINDOWS, ErrorCode::InvalidTouWindow);
+        for window in &windows {
+            window.validate()?;
+        }
+
+        let schedule = &mut ctx.accounts.schedule;
+
+        schedule.windows = windows;
+        schedule.updated_at = Clock::get()?.unix_timestamp;
+        schedule.bump = ctx.bumps.schedule;
+
+        emit!(TouScheduleUpdated {
+            windows: schedule.windows.clone(),
+            updated_at: schedule.updated_at,
+        });
+
+        msg!("Time-of-use schedule set with {} windows", schedule.windows.len());
+        Ok(())
+    }
+
+    /// Allow a grid operator key to publish demand-response events for one grid region
+    /// SECURITY: Only the protocol admin can register grid operators
+    pub fn register_grid_operator(
+        ctx: Context<RegisterGridOperator>,
+        authority: Pubkey,
+        grid_region: u16,
+    ) -> Result<()> {
+        let grid_operator = &mut ctx.accounts.grid_operator;
+
+        grid_operator.authority = authority;
+        grid_operator.registered_at = Clock::get()?.unix_timestamp;
+        grid_operator.bump = ctx.bumps.grid_operator;
+        grid_operator.grid_region = grid_region;
+
+        emit!(GridOperatorRegistered {
+            authority,
+            grid_region,
+        });
+
+        msg!("Grid operator {} registered for region {}", authority, grid_region);
+        Ok(())
+    }
+
+    /// Revoke a grid operator; events it already published stay in effect until they end
+    /// SECURITY: Only the protocol admin can remove grid operators
+    pub fn remove_grid_operator(ctx: Context<RemoveGridOperator>) -> Result<()> {
+        emit!(GridOperatorRemoved {
+            authority: ctx.accounts.grid_operator.authority,
+            grid_region: ctx.accounts.grid_operator.grid_region,
+        });
+
+        msg!("Grid operator {} removed", ctx.accounts.grid_operator.authority);
+        Ok(())
+    }
+
+    /// Publish a demand-response event with a temporary point multiplier in the operator's region
+    /// Each region has one event slot, which every session in the region reads; a new event can
+    /// replace one that has ended or was published by the same operator
+    pub fn publish_demand_response(
+        ctx: Context<PublishDemandResponse>,
+        event_id: u64,
+        start_time: i64,
+        end_time: i64,
+        multiplier_bps: u16,
+    ) -> Result<()> {
+        require!(
+            end_time > start_time && end_time - start_time <= MAX_DEMAND_RESPONSE_SECS,
+            ErrorCode::InvalidDemandResponseWindow
+        );
+        require!(
+            (MULTIPLIER_BPS_BASE..=MAX_MULTIPLIER_BPS).contains(&multiplier_bps),
+            ErrorCode::InvalidMultiplier
+        );
+
+        let event = &mut ctx.accounts.event;
+        let authority = ctx.accounts.authority.key();
+
+        require!(
+            event.grid_operator == Pubkey::default()
+                || event.grid_operator == authority
+                || Clock::get()?.unix_timestamp >= event.end_time,
+            ErrorCode::DemandResponseSlotInUse
+        );
+
+        event.grid_operator = authority;
+        event.grid_region = ctx.accounts.grid_operator.grid_region;
+        event.event_id = event_id;
+        event.start_time = start_time;
+        event.end_time = end_time;
+        event.multiplier_bps = multiplier_bps;
+        event.bump = ctx.bumps.event;
+
+        emit!(DemandResponsePublished {
+            grid_operator: authority,
+            grid_region: event.grid_region,
+            event_id,
+            start_time,
+            end_time,
+            multiplier_bps,
+        });
+
+        msg!("Demand response {} published for region {}: {} bps from {} to {}",
+             event_id, event.grid_region, multiplier_bps, start_time, end_time);
+        Ok(())
+    }
+
+    /// Cancel a demand-response event, returning its rent to the grid operator
+    pub fn cancel_demand_response(ctx: Context<CancelDemandResponse>) -> Result<()> {
+        emit!(DemandResponseCancelled {
+            grid_operator: ctx.accounts.event.grid_operator,
+            grid_region: ctx.accounts.event.grid_region,
+            event_id: ctx.accounts.event.event_id,
+        });
+
+        msg!("Demand response {} cancelled", ctx.accounts.event.event_id);
+        Ok(())
+    }
+
+    /// Allow an oracle key to publish grid carbon intensities alongside the admin
+    /// Pass the default pubkey to remove the oracle
+    pub fn set_carbon_oracle(
+        ctx: Context<UpdateProtocolConfig>,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.config.carbon_oracle = oracle;
+
+        emit!(CarbonOracleUpdated { oracle });
+
+        msg!("Carbon oracle set to {}", oracle);
+        Ok(())
+    }
+
+    /// Publish the carbon intensity of a grid region, creating its entry on first use
+    /// SECURITY: Only the protocol admin or the carbon oracle can publish intensities
+    pub fn set_carbon_intensity(
+        ctx: Context<SetCarbonIntensity>,
+        region: u16,
+        g_co2_per_kwh: u32,
+    ) -> Result<()> {
+        require!(
+            g_co2_per_kwh <= MAX_CARBON_INTENSITY_G_PER_KWH,
+            ErrorCode::InvalidCarbonIntensity
+        );
+
+        let carbon_intensity = &mut ctx.accounts.carbon_intensity;
+
+        carbon_intensity.region = region;
+        carbon_intensity.g_co2_per_kwh = g_co2_per_kwh;
+        carbon_intensity.updated_by = ctx.accounts.authority.key();
+        carbon_intensity.updated_at = Clock::get()?.unix_timestamp;
+        carbon_intensity.bump = ctx.bumps.carbon_intensity;
+
+        emit!(CarbonIntensityUpdated {
+            region,
+            g_co2_per_kwh,
+            updated_by: carbon_intensity.updated_by,
+            updated_at: carbon_intensity.updated_at,
+        });
+
+        msg!("Grid region {} carbon intensity set to {} gCO2/kWh", region, g_co2_per_kwh);
+        Ok(())
+    }
+
+    /// Allow a program to credit and debit points via CPI
+    /// The caller proves its identity by signing with the PDA derived from `authority_seed`
+    /// under `program_id`, and may mint at most `daily_mint_cap` points per UTC day
+    /// SECURITY: Only the protocol admin can authorize callers
+    pub fn register_authorized_caller(
+        ctx: Context<RegisterAuthorizedCaller>,
+        program_id: Pubkey,
+        authority_seed: Vec<u8>,
+        daily_mint_cap: u64,
+    ) -> Result<()> {
+        require!(
+            !authority_seed.is_empty() && authority_seed.len() <= MAX_CALLER_SEED_LEN,
+            ErrorCode::InvalidCallerSeed
+        );
+
+        let (authority, authority_bump) = Pubkey::find_program_address(&[&authority_seed], &program_id);
+        let authorized_caller = &mut ctx.accounts.authorized_caller;
+
+        authorized_caller.program_id = program_id;
+        authorized_caller.authority = authority;
+        authorized_caller.authority_seed = authority_seed;
+        authorized_caller.authority_bump = authority_bump;
+        authorized_caller.daily_mint_cap = daily_mint_cap;
+        authorized_caller.minted_today = 0;
+        authorized_caller.current_day = 0;
+        authorized_caller.bump = ctx.bumps.authorized_caller;
+
+        emit!(AuthorizedCallerRegistered {
+            program_id,
+            authority,
+            daily_mint_cap,
+        });
+
+        msg!("Authorized caller {} registered with authority {} (cap {} points/day)",
+             program_id, authority, daily_mint_cap);
+        Ok(())
+    }
+
+    /// Change an authorized caller's daily mint cap
+    /// SECURITY: Only the protocol admin can change caps
+    pub fn set_caller_mint_cap(
+        ctx: Context<UpdateAuthorizedCaller>,
+        daily_mint_cap: u64,
+    ) -> Result<()> {
+        let authorized_caller = &mut ctx.accounts.authorized_caller;
+
+        authorized_caller.daily_mint_cap = daily_mint_cap;
+
+        emit!(CallerMintCapUpdated {
+            program_id: authorized_caller.program_id,
+            daily_mint_cap,
+        });
+
+        msg!("Authorized caller {} cap set to {} points/day", authorized_caller.program_id, daily_mint_cap);
+        Ok(())
+    }
+
+    /// Revoke a program's permission to credit and debit points
+    /// SECURITY: Only the protocol admin can revoke callers
+    pub fn remove_authorized_caller(ctx: Context<RemoveAuthorizedCaller>) -> Result<()> {
+        emit!(AuthorizedCallerRemoved {
+            program_id: ctx.accounts.authorized_caller.program_id,
+        });
+
+        msg!("Authorized caller {} removed", ctx.accounts.authorized_caller.program_id);
+        Ok(())
+    }
+
+    /// Let a charger device key update and/or end this session on the driver's behalf
+    /// `scope` is a combination of DELEGATE_SCOPE_UPDATE and DELEGATE_SCOPE_END, and the
+    /// delegation lapses at `expires_at`; granting again replaces any existing delegate
+    pub fn grant_session_delegate(
+        ctx: Context<ManageSessionDelegate>,
+        delegate: Pubkey,
+        scope: u8,
+        expires_at: i64,
+    ) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+
+        require!(session.is_active, ErrorCode::SessionNotActive);
+        require!(
+            scope != 0 && scope & !(DELEGATE_SCOPE_UPDATE | DELEGATE_SCOPE_END) == 0,
+            ErrorCode::InvalidDelegateScope
+        );
+        require!(expires_at > Clock::get()?.unix_timestamp, ErrorCode::InvalidDelegateExpiry);
+
+        session.delegate = Some(delegate);
+        session.delegate_scope = scope;
+        session.delegate_expires_at = expires_at;
+
+        emit!(SessionDelegateGranted {
+            session: session.key(),
+            delegate,
+            scope,
+            expires_at,
+        });
+
+        msg!("Session delegate {} granted scope {} until {}", delegate, scope, expires_at);
+        Ok(())
+    }
+
+    /// Revoke the session's delegate
+    pub fn revoke_session_delegate(ctx: Context<ManageSessionDelegate>) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+
+        let delegate = session.delegate.take().ok_or(ErrorCode::NoSessionDelegate)?;
+        session.delegate_scope = 0;
+        session.delegate_expires_at = 0;
+
+        emit!(SessionDelegateRevoked {
+            session: session.key(),
+            delegate,
+        });
+
+        msg!("Session delegate {} revoked", delegate);
+        Ok(())
+    }
+
+    /// Pause a session, e.g. when the car is full but still plugged in
+    /// Energy updates are rejected while paused, and paused time beyond the station's grace
+    /// period is charged as an idle fee when the session ends
+    /// Pausing is optional: time since the last energy reading is charged the same way
+    /// Signed by the driver or by a session delegate with update permission
+    pub fn pause_session(ctx: Context<SessionControl>) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(session.is_active, ErrorCode::SessionNotActive);
+        session.check_authority(&ctx.accounts.authority.key(), DELEGATE_SCOPE_UPDATE, now)?;
+        require!(session.paused_at.is_none(), ErrorCode::SessionPaused);
+
+        session.paused_at = Some(now);
+
+        emit!(SessionPaused {
+            session: session.key(),
+            paused_at: now,
+        });
+
+        msg!("Session paused at {}", now);
+        Ok(())
+    }
+
+    /// Resume a paused session
+    /// Signed by the driver or by a session delegate with update permission
+    pub fn resume_session(ctx: Context<SessionControl>) -> Result<()> {
+        let session = &mut ctx.accounts.session;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(session.is_active, ErrorCode::SessionNotActive);
+        session.check_authority(&ctx.accounts.authority.key(), DELEGATE_SCOPE_UPDATE, now)?;
+        require!(session.paused_at.is_some(), ErrorCode::SessionNotPaused);
+
+        session.end_pause(now)?;
+        // No energy flows while paused, so the plausibility window restarts here, and the
+        // paused time is already billed through total_paused_secs
+        session.last_update_time = now;
+        session.last_energy_time = now;
+
+        emit!(SessionResumed {
+            session: session.key(),
+            resumed_at: now,
+            total_paused_secs: session.total_paused_secs,
+        });
+
+        msg!("Session resumed after {} seconds paused in total", session.total_paused_secs);
+        Ok(())
+    }
+
+    /// Permissionless crank that burns a driver's points once their lots pass the configured
+    /// lifetime; lots whose points the driver has already burned themselves are dropped
+    pub fn expire_points(ctx: Context<ExpirePoints>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+
+        let expired = user_account.take_expired_lots(Clock::get()?.unix_timestamp);
+        require!(expired > 0, ErrorCode::NoExpiredPoints);
+
+        let burned = expired.min(ctx.accounts.user_points_account.amount);
+        if burned > 0 {
+            burn_points(
+                &ctx.accounts.token_program,
+                &ctx.accounts.points_mint,
+                &ctx.accounts.user_points_account,
+                &ctx.accounts.points_authority,
+                ctx.bumps.points_authority,
+                burned,
+            )?;
+        }
+
+        user_account.expired_points = user_account.expired_points
+            .checked_add(burned)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(PointsExpired {
+            user_account: user_account.key(),
+            authority: user_account.authority,
+            amount: burned,
+        });
+
+        msg!("Expired {} points ({} burned)", expired, burned);
+        Ok(())
+    }
+
+    /// Create a fleet owned by the signer, with a points pool held by the fleet PDA
+    pub fn create_fleet(
+        ctx: Context<CreateFleet>,
+        fleet_id: u64,
+        name: String,
+    ) -> Result<()> {
+        require!(
+            !name.is_empty() && name.len() <= MAX_FLEET_NAME_LEN,
+            ErrorCode::InvalidFleetName
+        );
+
+        let fleet = &mut ctx.accounts.fleet;
+
+        fleet.owner = ctx.accounts.owner.key();
+        fleet.fleet_id = fleet_id;
+        fleet.name = name;
+        fleet.admins = Vec::new();
+        fleet.member_count = 0;
+        fleet.total_energy_wh = 0;
+        fleet.total_sessions = 0;
+        fleet.total_points_pooled = 0;
+        fleet.point_lots = Vec::new();
+        fleet.expired_points = 0;
+        fleet.created_at = Clock::get()?.unix_timestamp;
+        fleet.bump = ctx.bumps.fleet;
+
+        emit!(FleetCreated {
+            fleet: fleet.key(),
+            owner: fleet.owner,
+            name: fleet.name.clone(),
+        });
+
+        msg!("Fleet {} created by {}", fleet.name, fleet.owner);
+        Ok(())
+    }
+
+    /// Let another key manage the fleet's members
+    /// SECURITY: Only the fleet owner can add or remove admins
+    pub fn add_fleet_admin(
+        ctx: Context<ManageFleetAdmins>,
+        admin: Pubkey,
+    ) -> Result<()> {
+        let fleet = &mut ctx.accounts.fleet;
+
+        require!(!fleet.is_admin(&admin), ErrorCode::FleetAdminExists);
+        require!(fleet.admins.len() < MAX_FLEET_ADMINS, ErrorCode::TooManyFleetAdmins);
+
+        fleet.admins.push(admin);
+
+        emit!(FleetAdminAdded {
+            fleet: fleet.key(),
+            admin,
+        });
+
+        msg!("Fleet admin {} added", admin);
+        Ok(())
+    }
+
+    pub fn remove_fleet_admin(
+        ctx: Context<ManageFleetAdmins>,
+        admin: Pubkey,
+    ) -> Result<()> {
+        let fleet = &mut ctx.accounts.fleet;
+        let admin_count = fleet.admins.len();
+
+        fleet.admins.retain(|key| *key != admin);
+        require!(fleet.admins.len() < admin_count, ErrorCode::FleetAdminNotFound);
+
+        emit!(FleetAdminRemoved {
+            fleet: fleet.key(),
+            admin,
+        });
+
+        msg!("Fleet admin {} removed", admin);
+        Ok(())
+    }
+
+    /// Add a driver to the fleet; from then on their session points are paid into the fleet pool
+    /// The driver must co-sign, and can only belong to one fleet at a time
+    pub fn add_fleet_member(
+        ctx: Context<AddFleetMember>,
+        spending_limit_points: u64,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let fleet = &mut ctx.accounts.fleet;
+        let fleet_member = &mut ctx.accounts.fleet_member;
+
+        require!(user_account.fleet.is_none(), ErrorCode::AlreadyInFleet);
+
+        user_account.fleet = Some(fleet.key());
+        fleet.member_count = fleet.member_count
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+
+        fleet_member.fleet = fleet.key();
+        fleet_member.member = user_account.authority;
+        fleet_member.spending_limit_points = spending_limit_points;
+        fleet_member.points_spent = 0;
+        fleet_member.points_pooled = 0;
+        fleet_member.energy_wh = 0;
+        fleet_member.sessions = 0;
+        fleet_member.joined_at = Clock::get()?.unix_timestamp;
+        fleet_member.bump = ctx.bumps.fleet_member;
+
+        emit!(FleetMemberAdded {
+            fleet: fleet.key(),
+            member: fleet_member.member,
+            spending_limit_points,
+        });
+
+        msg!("Driver {} joined fleet {}", fleet_member.member, fleet.name);
+        Ok(())
+    }
+
+    /// Change how many pool points a member may withdraw over their membership
+    pub fn set_fleet_member_limit(
+        ctx: Context<SetFleetMemberLimit>,
+        spending_limit_points: u64,
+    ) -> Result<()> {
+        ctx.accounts.fleet_member.spending_limit_points = spending_limit_points;
+
+        emit!(FleetMemberLimitUpdated {
+            fleet: ctx.accounts.fleet.key(),
+            member: ctx.accounts.fleet_member.member,
+            spending_limit_points,
+        });
+
+        msg!("Fleet member {} spending limit set to {} points",
+             ctx.accounts.fleet_member.member, spending_limit_points);
+        Ok(())
+    }
+
+    /// Remove a driver from the fleet; signed by a fleet admin or the member leaving
+    /// Points already in the pool stay with the fleet, as do those of sessions still running,
+    /// and the member account's rent goes back to the fleet owner
+    pub fn remove_fleet_member(ctx: Context<RemoveFleetMember>) -> Result<()> {
+        let fleet = &mut ctx.accounts.fleet;
+        let member = ctx.accounts.fleet_member.member;
+
+        ctx.accounts.user_account.fleet = None;
+        fleet.member_count = fleet.member_count.saturating_sub(1);
+
+        emit!(FleetMemberRemoved {
+            fleet: fleet.key(),
+            member,
+            removed_by: ctx.accounts.authority.key(),
+        });
+
+        msg!("Driver {} left fleet {}", member, fleet.name);
+        Ok(())
+    }
+
+    /// Withdraw points from the fleet pool to a member, within their spending limit
+    /// The spending limit is what bounds withdrawals, so they do not count against the member's
+    /// daily transfer limit; the points keep the expiry they had in the pool
+    pub fn withdraw_fleet_points(
+        ctx: Context<WithdrawFleetPoints>,
+        amount: u64,
+    ) -> Result<()> {
+        let fleet_member = &mut ctx.accounts.fleet_member;
+
+        let points_spent = fleet_member.points_spent
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(
+            points_spent <= fleet_member.spending_limit_points,
+            ErrorCode::FleetSpendingLimitExceeded
+        );
+        require!(
+            ctx.accounts.fleet_points_account.amount >= amount,
+            ErrorCode::InsufficientPoints
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let user_account = &mut ctx.accounts.user_account;
+
+        fleet_member.points_spent = points_spent;
+
+        move_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.fleet_points_account,
+            &ctx.accounts.member_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            amount,
+        )?;
+
+        let fleet = &mut ctx.accounts.fleet;
+        let moved_lots = spend_lots(&mut fleet.point_lots, amount);
+        for lot in &moved_lots {
+            user_account.add_lot(lot.amount, now, lot.expires_at)?;
+        }
+        user_account.credit_lot(
+            amount - lots_total(&moved_lots),
+            now,
+            ctx.accounts.config.point_lifetime_secs,
+        )?;
+
+        emit!(FleetPointsWithdrawn {
+            fleet: fleet.key(),
+            member: fleet_member.member,
+            amount,
+            points_spent,
+        });
+
+        msg!("Member {} withdrew {} fleet points ({} of {} spent)",
+             fleet_member.member, amount, points_spent, fleet_member.spending_limit_points);
+        Ok(())
+    }
+
+    /// Permissionless crank that burns expired points from a fleet pool
+    pub fn expire_fleet_points(ctx: Context<ExpireFleetPoints>) -> Result<()> {
+        let fleet = &mut ctx.accounts.fleet;
+
+        let expired = take_expired_lots(&mut fleet.point_lots, Clock::get()?.unix_timestamp);
+        require!(expired > 0, ErrorCode::NoExpiredPoints);
+
+        let burned = expired.min(ctx.accounts.fleet_points_account.amount);
+        if burned > 0 {
+            burn_points(
+                &ctx.accounts.token_program,
+                &ctx.accounts.points_mint,
+                &ctx.accounts.fleet_points_account,
+                &ctx.accounts.points_authority,
+                ctx.bumps.points_authority,
+                burned,
+            )?;
+        }
+
+        fleet.expired_points = fleet.expired_points
+            .checked_add(burned)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(FleetPointsExpired {
+            fleet: fleet.key(),
+            amount: burned,
+        });
+
+        msg!("Expired {} fleet points ({} burned)", expired, burned);
+        Ok(())
+    }
+
+    /// Set the season length and the prize points paid by leaderboard rank
+    /// Prizes are fixed when a season closes; the length applies to seasons started afterwards
+    pub fn set_season_rewards(
+        ctx: Context<UpdateProtocolConfig>,
+        duration_secs: u32,
+        prizes: Vec<u64>,
+    ) -> Result<()> {
+        require!(
+            duration_secs > 0 && prizes.len() <= MAX_SEASON_ENTRIES,
+            ErrorCode::InvalidSeasonTerms
+        );
+
+        let config = &mut ctx.accounts.config;
+
+        config.season_duration_secs = duration_secs;
+        config.season_prizes = prizes;
+
+        emit!(SeasonRewardsUpdated {
+            duration_secs,
+            prizes: config.season_prizes.clone(),
+        });
+
+        msg!("Season rewards set: {}s seasons, {} prizes",
+             duration_secs, config.season_prizes.len());
+        Ok(())
+    }
+
+    /// Start the first season; later seasons are started by close_season
+    pub fn start_season(ctx: Context<StartSeason>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+
+        require!(config.current_season == 0, ErrorCode::SeasonAlreadyRunning);
+        require!(config.season_duration_secs > 0, ErrorCode::InvalidSeasonTerms);
+
+        open_season(&mut ctx.accounts.season, config, ctx.bumps.season, Clock::get()?.unix_timestamp)
+    }
+
+    /// Close the current season once it is over, fixing its winners and their prizes,
+    /// and start the next one
+    /// Permissionless so seasons roll over without the admin; the admin may also close a
+    /// season early. Sessions ending after the season is over close it themselves
+    pub fn close_season(ctx: Context<CloseSeason>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.config;
+
+        require!(
+            now >= ctx.accounts.season.end_time || ctx.accounts.cranker.key() == config.admin,
+            ErrorCode::SeasonNotOver
+        );
+
+        roll_over_season(&mut ctx.accounts.season, &mut ctx.accounts.next_season, config, ctx.bumps.next_season, now)
+    }
+
+    /// Pay a winner of a closed season their prize points
+    /// Permissionless so prizes can be pushed to every winner by one crank
+    pub fn distribute_season_prize(ctx: Context<DistributeSeasonPrize>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let user_account = &mut ctx.accounts.user_account;
+
+        require!(season.is_closed, ErrorCode::SeasonNotClosed);
+
+        let rank = season.leaderboard.iter()
+            .position(|entry| entry.user == user_account.authority)
+            .ok_or(ErrorCode::NotASeasonWinner)?;
+        let prize = season.prizes.get(rank).copied().unwrap_or(0);
+        require!(prize > 0, ErrorCode::NotASeasonWinner);
+        require!(season.prizes_paid & (1 << rank) == 0, ErrorCode::SeasonPrizeAlreadyPaid);
+
+        season.prizes_paid |= 1 << rank;
+
+        mint_points(
+            &ctx.accounts.token_program,
+            &ctx.accounts.points_mint,
+            &ctx.accounts.user_points_account,
+            &ctx.accounts.points_authority,
+            ctx.bumps.points_authority,
+            prize,
+        )?;
+
+        user_account.total_points = user_account.total_points
+            .checked_add(prize)
+            .ok_or(ErrorCode::Overflow)?;
+        user_account.credit_lot(prize, Clock::get()?.unix_timestamp, ctx.accounts.config.point_lifetime_secs)?;
+
+        emit!(SeasonPrizePaid {
+            season_id: season.season_id,
+            user: user_account.authority,
+            rank: rank as u8 + 1,
+            points: prize,
+        });
+
+        msg!("Season {} prize of {} points paid to #{} {}",
+             season.season_id, prize, rank + 1, user_account.authority);
+        Ok(())
+    }
+
+    /// Grow a user account created before the latest fields were added to UserAccount
+    /// The new fields start zeroed; anyone may pay the extra rent
+    pub fn upgrade_user_account(ctx: Context<UpgradeUserAccount>) -> Result<()> {
+        let user_account = &ctx.accounts.user_account;
+        let old_len = user_account.data_len();
+
+        grow_account(
+            user_account,
+            UserAccount::DISCRIMINATOR,
+            8 + UserAccount::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        // Fails the upgrade unless the grown account reads back as a UserAccount
+        let upgraded = UserAccount::try_deserialize(&mut &user_account.try_borrow_data()?[..])?;
+
+        msg!("User account {} upgraded from {} to {} bytes",
+             upgraded.authority, old_len, user_account.data_len());
+        Ok(())
+    }
+
+    /// Grow the protocol config created before the latest fields were added to ProtocolConfig
+    /// The new settings start zeroed (disabled) until the admin sets them
+    /// SECURITY: Only the config admin can upgrade it
+    pub fn upgrade_protocol_config(ctx: Context<UpgradeProtocolConfig>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let old_len = config.data_len();
+
+        grow_account(
+            config,
+            ProtocolConfig::DISCRIMINATOR,
+            8 + ProtocolConfig::INIT_SPACE,
+            &ctx.accounts.admin,
+            &ctx.accounts.system_program,
+        )?;
+
+        let upgraded = ProtocolConfig::try_deserialize(&mut &config.try_borrow_data()?[..])?;
+        require_keys_eq!(upgraded.admin, ctx.accounts.admin.key(), ErrorCode::UnauthorizedAdmin);
+
+        msg!("Protocol config upgraded from {} to {} bytes", old_len, config.data_len());
+        Ok(())
+    }
+
+    /// Grow a session opened before the latest fields were added to ChargingSession, so it
+    /// can still be ended, expired and closed
+    /// Launch sessions were seeded with their start time, which becomes seed_timestamp
+    pub fn upgrade_session(ctx: Context<UpgradeSession>) -> Result<()> {
+        let session = &ctx.accounts.session;
+        let old_len = session.data_len();
+
+        grow_account(
+            session,
+            ChargingSession::DISCRIMINATOR,
+            8 + ChargingSession::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        let mut upgraded = ChargingSession::try_deserialize(&mut &session.try_borrow_data()?[..])?;
+        if upgraded.seed_timestamp == 0 {
+            upgraded.seed_timestamp = upgraded.start_time;
+        }
+        if upgraded.last_energy_time == 0 {
+            upgraded.last_energy_time = upgraded.last_update_time.max(upgraded.start_time);
+        }
+        let expected = Pubkey::create_program_address(
+            &[
+                b"session".as_ref(),
+                upgraded.user.as_ref(),
+                &upgraded.seed_timestamp.to_le_bytes(),
+                &upgraded.nonce.to_le_bytes(),
+                &[upgraded.bump],
+            ],
+            &crate::ID,
+        ).map_err(|_| ErrorCode::InvalidAccountUpgrade)?;
+        require_keys_eq!(session.key(), expected, ErrorCode::InvalidAccountUpgrade);
+
+        upgraded.try_serialize(&mut &mut session.try_borrow_mut_data()?[..])?;
+
+        msg!("Session {} upgraded from {} to {} bytes", session.key(), old_len, session.data_len());
+        Ok(())
+    }
+}
+
+/// Mark a session ended, settle its escrow and update the driver's lifetime stats and achievements
+/// Shared by end_session and expire_session; the earned points are minted by pay_session_points
+fn finish_session<'info>(
+    session: &mut ChargingSession,
+    user_account: &mut UserAccount,
+    escrow: Option<&Account<'info, SessionEscrow>>,
+    operator: Option<&AccountInfo<'info>>,
+    carbon_intensity: Option<&CarbonIntensity>,
+    config: &ProtocolConfig,
+    end_time: i64,
+) -> Result<()> {
+    // Before end_pause: a pause still open at the end is part of the time since the last reading
+    session.idle_fee_lamports = session.idle_fee_due_lamports(end_time)?;
+    session.end_pause(end_time)?;
+    if let Some(carbon_intensity) = carbon_intensity {
+        session.settle_carbon(carbon_intensity.g_co2_per_kwh)?;
+    }
+    // The driver's tier before this session counts; it is recomputed below
+    let benefits = config.loyalty_benefits(user_account.loyalty_tier);
+    session.apply_loyalty_multiplier(benefits.points_multiplier_bps)?;
+    session.end_time = Some(end_time);
+    session.is_active = false;
+
+    if session.deposit_lamports > 0 {
+        let escrow = escrow.ok_or(ErrorCode::EscrowMismatch)?;
+        let operator = operator.ok_or(ErrorCode::EscrowMismatch)?;
+
+        require_keys_eq!(operator.key(), escrow.operator, ErrorCode::InvalidOperator);
+
+        // The deposit is all the program can collect; any idle fee beyond it is left to the operator
+        let amount_due = session.amount_due_lamports()?.min(escrow.deposit_lamports);
+
+        // Pay operator from escrow
+        **escrow.to_account_info().try_borrow_mut_lamports()? -= amount_due;
+        **operator.try_borrow_mut_lamports()? += amount_due;
+
+        // The close constraint on escrow refunds the unused deposit and rent to the driver
+        session.amount_paid_lamports = amount_due;
+
+        msg!("Settled {} lamports to operator, {} lamports refunded",
+             amount_due, escrow.deposit_lamports - amount_due);
+    }
+
+    user_account.total_points = user_account.total_points
+        .checked_add(session.points_earned)
+        .ok_or(ErrorCode::Overflow)?;
+
+    // Carry the sub-kWh remainder into the next session instead of truncating each one
+    let energy_wh = session.energy_consumed_wh
+        .checked_add(user_account.energy_remainder_wh as u64)
+        .ok_or(ErrorCode::Overflow)?;
+    user_account.total_energy_kwh = user_account.total_energy_kwh
+        .checked_add(energy_wh / 1000)
+        .ok_or(ErrorCode::Overflow)?;
+    user_account.energy_remainder_wh = (energy_wh % 1000) as u16;
+
+    user_account.total_exported_wh = user_account.total_exported_wh
+        .checked_add(session.energy_exported_wh)
+        .ok_or(ErrorCode::Overflow)?;
+    user_account.total_co2_avoided_g = user_account.total_co2_avoided_g
+        .checked_add(session.co2_avoided_g)
+        .ok_or(ErrorCode::Overflow)?;
+
+    // A session that moved no energy is settled but does not count towards stats or rewards
+    let delivered_energy = session.delivered_energy();
+    if delivered_energy {
+        user_account.total_sessions = user_account.total_sessions
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+    }
+
+    let longest_streak_days = user_account.longest_streak_days;
+    if delivered_energy && user_account.record_session_day(end_time)? {
+        let streak_days = user_account.current_streak_days;
+        // Milestones pay once per driver: a streak rebuilt after a reset earns nothing until it
+        // goes past the driver's longest streak
+        if let Some(milestone) = config.streak_milestones
+            .iter()
+            .find(|milestone| milestone.days == streak_days && streak_days > longest_streak_days)
+        {
+            session.streak_bonus_points = milestone.bonus_points;
+
+            emit!(StreakMilestoneReached {
+                user: user_account.authority,
+                streak_days,
+                bonus_points: milestone.bonus_points,
+            });
+        }
+    }
+
+    let loyalty_tier = config.loyalty_tier_for(user_account);
+    if loyalty_tier != user_account.loyalty_tier {
+        msg!("Loyalty tier changed from {} to {}", user_account.loyalty_tier, loyalty_tier);
+        user_account.loyalty_tier = loyalty_tier;
+    }
+
+    let unlocked = if delivered_energy {
+        earned_achievements(user_account, session, end_time) & !user_account.achievements
+    } else {
+        0
+    };
+    for (index, bonus_points) in ACHIEVEMENT_BONUS_POINTS.iter().enumerate() {
+        let achievement = 1 << index;
+        if unlocked & achievement == 0 {
+            continue;
+        }
+
+        session.achievement_bonus_points = session.achievement_bonus_points
+            .checked_add(*bonus_points)
+            .ok_or(ErrorCode::Overflow)?;
+
+        emit!(AchievementUnlocked {
+            user: user_account.authority,
+            achievement,
+            bonus_points: *bonus_points,
+            unlocked_at: end_time,
+        });
+    }
+    user_account.achievements |= unlocked;
+    user_account.total_points = user_account.total_points
+        .checked_add(session.achievement_bonus_points)
+        .and_then(|total| total.checked_add(session.streak_bonus_points))
+        .ok_or(ErrorCode::Overflow)?;
+
+    // Points paid into a fleet pool are tracked in the fleet's own lots
+    if session.fleet.is_none() {
+        user_account.credit_lot(session.points_to_mint()?, end_time, config.point_lifetime_secs)?;
+    }
+
+    Ok(())
+}
+
+/// Accounts pay_session_points mints a finished session's points with
+struct SessionPointsAccounts<'a, 'info> {
+    fleet: Option<&'a mut Account<'info, Fleet>>,
+    fleet_member: Option<&'a mut Account<'info, FleetMember>>,
+    fleet_points_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    referrer_account: Option<&'a mut UserAccount>,
+    referrer_points_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    user_points_account: &'a InterfaceAccount<'info, TokenAccount>,
+    points_mint: &'a InterfaceAccount<'info, Mint>,
+    points_authority: &'a AccountInfo<'info>,
+    points_authority_bump: u8,
+    token_program: &'a Program<'info, Token2022>,
+}
+
+/// Mint a finished session's points to the driver, or to the fleet pool for sessions started
+/// by a fleet member, and pay the driver's referrer their share
+/// Shared by end_session and expire_session, after finish_session and record_season_session
+fn pay_session_points(
+    session: &Account<ChargingSession>,
+    user_account: &mut UserAccount,
+    accounts: SessionPointsAccounts,
+    config: &ProtocolConfig,
+    now: i64,
+) -> Result<()> {
+    let fleet = record_fleet_session(
+        session,
+        user_account,
+        accounts.fleet,
+        accounts.fleet_member,
+        config,
+        now,
+    )?;
+    let points_account = match fleet {
+        Some(fleet) => {
+            let fleet_points_account = accounts.fleet_points_account
+                .ok_or(ErrorCode::FleetAccountMissing)?;
+            require_keys_eq!(fleet_points_account.owner, fleet, ErrorCode::InvalidFleetAccount);
+            fleet_points_account
+        }
+        None => accounts.user_points_account,
+    };
+
+    mint_points(
+        accounts.token_program,
+        accounts.points_mint,
+        points_account,
+        accounts.points_authority,
+        accounts.points_authority_bump,
+        session.points_to_mint()?,
+    )?;
+
+    let referral_points = settle_referral(session, user_account, accounts.referrer_account, config, now)?;
+    if referral_points > 0 {
+        let referrer_points_account = accounts.referrer_points_account
+            .ok_or(ErrorCode::ReferrerAccountMissing)?;
+        require!(
+            user_account.referrer == Some(referrer_points_account.owner),
+            ErrorCode::InvalidReferrer
+        );
+
+        mint_points(
+            accounts.token_program,
+            accounts.points_mint,
+            referrer_points_account,
+            accounts.points_authority,
+            accounts.points_authority_bump,
+            referral_points,
+        )?;
+
+        emit!(ReferralRewarded {
+            referrer: referrer_points_account.owner,
+            referee: user_account.authority,
+            session: session.key(),
+            points: referral_points,
+        });
+    }
+
+    Ok(())
+}
+
+/// Record a finished session against the driver's referral and credit the referrer's share
+/// Referrers earn a share of session points, excluding bonuses, for the referee's first
+/// sessions until either the session or energy limit is reached, up to a per-referee cap
+/// Returns the points to mint to the referrer
+fn settle_referral(
+    session: &ChargingSession,
+    user_account: &mut UserAccount,
+    referrer_account: Option<&mut UserAccount>,
+    config: &ProtocolConfig,
+    now: i64,
+) -> Result<u64> {
+    let terms = &config.referral_terms;
+    let Some(referrer) = user_account.referrer else {
+        return Ok(0);
+    };
+    // Sessions that delivered nothing do not use up one of the referee's rewarded sessions
+    if !session.delivered_energy() {
+        return Ok(0);
+    }
+    if user_account.referral_sessions >= terms.max_sessions
+        || user_account.referral_energy_wh >= terms.max_energy_kwh.saturating_mul(1000)
+    {
+        return Ok(0);
+    }
+
+    let referrer_account = referrer_account.ok_or(ErrorCode::ReferrerAccountMissing)?;
+    require_keys_eq!(referrer_account.authority, referrer, ErrorCode::InvalidReferrer);
+
+    let share = session.points_earned as u128 * terms.reward_pct as u128 / 100;
+    let remaining = terms.max_points_per_referee.saturating_sub(user_account.referral_points_paid);
+    let points = (share as u64).min(remaining);
+
+    user_account.referral_sessions += 1;
+    user_account.referral_energy_wh = user_account.referral_energy_wh
+        .checked_add(session.energy_consumed_wh)
+        .ok_or(ErrorCode::Overflow)?;
+    user_account.referral_points_paid = user_account.referral_points_paid
+        .checked_add(points)
+        .ok_or(ErrorCode::Overflow)?;
+
+    referrer_account.referral_points_earned = referrer_account.referral_points_earned
+        .checked_add(points)
+        .ok_or(ErrorCode::Overflow)?;
+    referrer_account.total_points = referrer_account.total_points
+        .checked_add(points)
+        .ok_or(ErrorCode::Overflow)?;
+    referrer_account.credit_lot(points, now, config.point_lifetime_secs)?;
+
+    Ok(points)
+}
+
+/// Grow an account of this program written with an older, shorter layout to `new_len`
+/// Appended bytes are zeroed, which decodes as 0 / None / empty for every field type we append;
+/// the payer tops the account up to the rent-exempt minimum for its new size
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    discriminator: &[u8],
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    require_keys_eq!(*account.owner, crate::ID, ErrorCode::InvalidAccountUpgrade);
+    {
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() >= discriminator.len() && data[..discriminator.len()] == *discriminator,
+            ErrorCode::InvalidAccountUpgrade
+        );
+    }
+    require!(account.data_len() < new_len, ErrorCode::AccountUpToDate);
+
+    let rent_due = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &payer.key(),
+            &account.key(),
+            rent_due,
+        );
+
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[
+                payer.to_account_info(),
+                account.clone(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Deserialize a program account at a PDA that may not have been created yet
+/// Used for per-region accounts whose address is fixed by seeds, so the caller cannot leave
+/// them out; an address that was never initialized reads as None
+fn load_if_exists<T: AccountDeserialize + Owner>(account: &AccountInfo) -> Result<Option<T>> {
+    if account.data_is_empty() {
+        return Ok(None);
+    }
+    require_keys_eq!(*account.owner, T::owner(), ErrorCode::InvalidAccountOwner);
+    Ok(Some(T::try_deserialize(&mut &account.try_borrow_data()?[..])?))
+}
+
+/// Initialize the season after config.current_season and make it current
+fn open_season(season: &mut Season, config: &mut ProtocolConfig, bump: u8, now: i64) -> Result<()> {
+    season.season_id = config.current_season
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    season.start_time = now;
+    season.end_time = now
+        .checked_add(config.season_duration_secs as i64)
+        .ok_or(ErrorCode::Overflow)?;
+    season.leaderboard = Vec::new();
+    season.is_closed = false;
+    season.prizes = Vec::new();
+    season.prizes_paid = 0;
+    season.bump = bump;
+
+    config.current_season = season.season_id;
+
+    emit!(SeasonStarted {
+        season_id: season.season_id,
+        start_time: season.start_time,
+        end_time: season.end_time,
+    });
+
+    msg!("Season {} started, ends at {}", season.season_id, season.end_time);
+    Ok(())
+}
+
+/// Close `season`, fixing its winners and their prizes, and make `next_season` current
+fn roll_over_season(
+    season: &mut Season,
+    next_season: &mut Season,
+    config: &mut ProtocolConfig,
+    next_season_bump: u8,
+    now: i64,
+) -> Result<()> {
+    season.is_closed = true;
+    season.end_time = season.end_time.min(now);
+    season.prizes = config.season_prizes.iter()
+        .take(season.leaderboard.len())
+        .copied()
+        .collect();
+
+    emit!(SeasonClosed {
+        season_id: season.season_id,
+        winners: season.leaderboard.clone(),
+        prizes: season.prizes.clone(),
+    });
+
+    msg!("Season {} closed with {} ranked drivers", season.season_id, season.leaderboard.len());
+
+    open_season(next_season, config, next_season_bump, now)
+}
+
+/// Add an ended session to the driver's season totals and the season leaderboard
+/// A session ending after the season is over first closes it and starts the next season,
+/// which the session then counts towards
+/// Drivers are ranked on session points, without one-off achievement and streak bonuses
+fn record_season_session<'info>(
+    session: &Account<ChargingSession>,
+    user_account: &mut UserAccount,
+    season: Option<&mut Account<'info, Season>>,
+    next_season: Option<&mut Account<'info, Season>>,
+    next_season_bump: Option<u8>,
+    config: &mut ProtocolConfig,
+    now: i64,
+) -> Result<()> {
+    let Some(season) = season else {
+        require!(config.current_season == 0, ErrorCode::SeasonAccountMissing);
+        require!(next_season.is_none(), ErrorCode::SeasonNotOver);
+        return Ok(());
+    };
+    let season = if now >= season.end_time {
+        let next_season = next_season.ok_or(ErrorCode::NextSeasonMissing)?;
+        let bump = next_season_bump.ok_or(ErrorCode::NextSeasonMissing)?;
+        roll_over_season(season, next_season, config, bump, now)?;
+        next_season
+    } else {
+        require!(next_season.is_none(), ErrorCode::SeasonNotOver);
+        season
+    };
+    if !session.delivered_energy() {
+        return Ok(());
+    }
+
+    if user_account.season_id != season.season_id {
+        user_account.season_id = season.season_id;
+        user_account.season_energy_wh = 0;
+        user_account.season_points = 0;
+    }
+    user_account.season_energy_wh = user_account.season_energy_wh
+        .checked_add(session.energy_consumed_wh)
+        .ok_or(ErrorCode::Overflow)?;
+    user_account.season_points = user_account.season_points
+        .checked_add(session.points_earned)
+        .ok_or(ErrorCode::Overflow)?;
+
+    season.record(SeasonEntry {
+        user: user_account.authority,
+        energy_wh: user_account.season_energy_wh,
+        points: user_account.season_points,
+    });
+
+    Ok(())
+}
+
+/// Add a fleet member's session to the fleet and member aggregates
+/// Returns the fleet whose pool receives the session points, or None for drivers outside a fleet
+fn record_fleet_session(
+    session: &Account<ChargingSession>,
+    user_account: &UserAccount,
+    fleet: Option<&mut Account<Fleet>>,
+    fleet_member: Option<&mut Account<FleetMember>>,
+    config: &ProtocolConfig,
+    now: i64,
+) -> Result<Option<Pubkey>> {
+    let Some(fleet_key) = session.fleet else {
+        return Ok(None);
+    };
+    let fleet = fleet.ok_or(ErrorCode::FleetAccountMissing)?;
+    require_keys_eq!(fleet.key(), fleet_key, ErrorCode::InvalidFleetAccount);
+
+    let points = session.points_to_mint()?;
+
+    // Member stats are only kept while the driver is still in the fleet; a driver who left
+    // during the session has no member account any more, but the points stay with the pool
+    if user_account.fleet == Some(fleet_key) {
+        let fleet_member = fleet_member.ok_or(ErrorCode::FleetAccountMissing)?;
+        require_keys_eq!(fleet_member.fleet, fleet_key, ErrorCode::InvalidFleetAccount);
+        require_keys_eq!(fleet_member.member, user_account.authority, ErrorCode::InvalidFleetAccount);
+
+        fleet_member.energy_wh = fleet_member.energy_wh
+            .checked_add(session.energy_consumed_wh)
+            .ok_or(ErrorCode::Overflow)?;
+        fleet_member.sessions = fleet_member.sessions
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        fleet_member.points_pooled = fleet_member.points_pooled
+            .checked_add(points)
+            .ok_or(ErrorCode::Overflow)?;
+    }
+
+    fleet.total_energy_wh = fleet.total_energy_wh
+        .checked_add(session.energy_consumed_wh)
+        .ok_or(ErrorCode::Overflow)?;
+    fleet.total_sessions = fleet.total_sessions
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    fleet.total_points_pooled = fleet.total_points_pooled
+        .checked_add(points)
+        .ok_or(ErrorCode::Overflow)?;
+    push_lot(&mut fleet.point_lots, points, now, lot_expiry(now, config.point_lifetime_secs))?;
+
+    emit!(FleetPointsPooled {
+        fleet: fleet_key,
+        member: user_account.authority,
+        session: session.key(),
+        points,
+    });
+
+    Ok(Some(fleet_key))
+}
+
+/// Achievements the driver qualifies for once `session` has been counted in their stats
+fn earned_achievements(user_account: &UserAccount, session: &ChargingSession, end_time: i64) -> u32 {
+    let mut earned = 0;
+
+    if user_account.total_sessions >= 1 {
+        earned |= ACHIEVEMENT_FIRST_SESSION;
+    }
+    if user_account.total_energy_kwh >= 100 {
+        earned |= ACHIEVEMENT_100_KWH;
+    }
+    if user_account.current_streak_days >= 7 {
+        earned |= ACHIEVEMENT_7_DAY_STREAK;
+    }
+    if session.charger_power_kw >= FAST_CHARGER_MIN_KW {
+        earned |= ACHIEVEMENT_FAST_CHARGER;
+    }
+    // Uses the on-chain end time, which the driver cannot choose
+    if end_time.rem_euclid(SECONDS_PER_DAY) / 3600 < NIGHT_OWL_END_HOUR {
+        earned |= ACHIEVEMENT_NIGHT_OWL;
+    }
+
+    earned
+}
+
+/// Most energy (Wh) a charger of the given power can deliver in `elapsed_seconds`,
+/// relaxed by the station's tolerance percentage
+pub fn max_plausible_energy_wh(charger_power_kw: u16, energy_tolerance_pct: u8, elapsed_seconds: i64) -> u64 {
+    let elapsed_seconds = elapsed_seconds.max(0) as u128;
+    let max_wh = charger_power_kw as u128 * 1000 * elapsed_seconds * (100 + energy_tolerance_pct as u128)
+        / (3600 * 100);
+    u64::try_from(max_wh).unwrap_or(u64::MAX)
+}
+
+/// Mint points tokens, signed by the points authority PDA
+fn mint_points<'info>(
+    token_program: &Program<'info, Token2022>,
+    points_mint: &InterfaceAccount<'info, Mint>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    points_authority: &AccountInfo<'info>,
+    points_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let signer_seeds: &[&[&[u8]]] = &[&[POINTS_AUTHORITY_SEED, &[points_authority_bump]]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        MintTo {
+            mint: points_mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: points_authority.clone(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::mint_to(cpi_ctx, amount)
+}
+
+/// Burn points tokens, signed by the points authority PDA as permanent delegate
+fn burn_points<'info>(
+    token_program: &Program<'info, Token2022>,
+    points_mint: &InterfaceAccount<'info, Mint>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    points_authority: &AccountInfo<'info>,
+    points_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let signer_seeds: &[&[&[u8]]] = &[&[POINTS_AUTHORITY_SEED, &[points_authority_bump]]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Burn {
+            mint: points_mint.to_account_info(),
+            from: from.to_account_info(),
+            authority: points_authority.clone(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::burn(cpi_ctx, amount)
+}
+
+/// Move points between token accounts of the non-transferable mint by burning them from one
+/// and minting them to the other
+fn move_points<'info>(
+    token_program: &Program<'info, Token2022>,
+    points_mint: &InterfaceAccount<'info, Mint>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    points_authority: &AccountInfo<'info>,
+    points_authority_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    burn_points(token_program, points_mint, from, points_authority, points_authority_bump, amount)?;
+    mint_points(token_program, points_mint, to, points_authority, points_authority_bump, amount)
+}
+
+/// Build the message a meter signs to attest a reading for a session
+pub fn meter_reading_message(
+    session: &Pubkey,
+    reading_sequence: u64,
+    cumulative_energy_wh: u64,
+    cumulative_exported_wh: u64,
+) -> [u8; METER_READING_MESSAGE_LEN] {
+    let mut message = [0u8; METER_READING_MESSAGE_LEN];
+    message[..32].copy_from_slice(session.as_ref());
+    message[32..40].copy_from_slice(&reading_sequence.to_le_bytes());
+    message[40..48].copy_from_slice(&cumulative_energy_wh.to_le_bytes());
+    message[48..56].copy_from_slice(&cumulative_exported_wh.to_le_bytes());
+    message
+}
+
+/// Check that the instruction preceding the current one is an Ed25519 precompile
+/// verification of `message` signed by `meter`. The precompile itself fails the whole
+/// transaction on a bad signature, so we only need to confirm what it verified
+#[allow(deprecated)]
+fn verify_meter_signature(
+    instructions_sysvar: &AccountInfo,
+    meter: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = sysvar_instructions::load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, ErrorCode::MissingMeterSignature);
+
+    let ed25519_index = current_index - 1;
+    let ed25519_ix = sysvar_instructions::load_instruction_at_checked(
+        ed25519_index as usize,
+        instructions_sysvar,
+    )?;
+    require!(
+        ed25519_ix.program_id == ed25519_program::ID,
+        ErrorCode::MissingMeterSignature
+    );
+
+    // Exactly one signature whose offsets point into the precompile instruction itself
+    let data = &ed25519_ix.data;
+    require!(
+        data.len() >= ED25519_OFFSETS_START + ED25519_OFFSETS_SIZE && data[0] == 1,
+        ErrorCode::InvalidMeterSignature
+    );
+
+    let read_u16 = |at: usize| {
+        let start = ED25519_OFFSETS_START + at;
+        u16::from_le_bytes([data[start], data[start + 1]])
+    };
+    let signature_ix_index = read_u16(2);
+    let public_key_offset = read_u16(4) as usize;
+    let public_key_ix_index = read_u16(6);
+    let message_offset = read_u16(8) as usize;
+    let message_size = read_u16(10) as usize;
+    let message_ix_index = read_u16(12);
+
+    for ix_index in [signature_ix_index, public_key_ix_index, message_ix_index] {
+        require!(
+            ix_index == u16::MAX || ix_index == ed25519_index,
+            ErrorCode::InvalidMeterSignature
+        );
+    }
+
+    let signer = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(ErrorCode::InvalidMeterSignature)?;
+    require!(signer == meter.as_ref(), ErrorCode::InvalidMeterSignature);
+
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ErrorCode::InvalidMeterSignature)?;
+    require!(signed_message == message, ErrorCode::InvalidMeterSignature);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(timestamp: i64, nonce: u32)]
+pub struct StartSession<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = 8 + ChargingSession::INIT_SPACE,
+        seeds = [b"session", user.key().as_ref(), &timestamp.to_le_bytes(), &nonce.to_le_bytes()],
+        bump
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    /// Only required for prepaid sessions (deposit_lamports > 0)
+    #[account(
+        init,
+        payer = user,
+        space = 8 + SessionEscrow::INIT_SPACE,
+        seeds = [b"escrow", session.key().as_ref()],
+        bump
+    )]
+    pub escrow: Option<Account<'info, SessionEscrow>>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [b"station", station.code.as_bytes()],
+        bump = station.bump
+    )]
+    pub station: Account<'info, ChargerStation>,
+
+    #[account(
+        seeds = [b"meter", meter_registration.meter.as_ref()],
+        bump = meter_registration.bump
+    )]
+    pub meter_registration: Account<'info, MeterRegistration>,
+
+    /// Fleet membership is fixed for the session here, so leaving the fleet before
+    /// end_session does not move the session's points out of the pool
+    #[account(
+        seeds = [b"user", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// Time-of-use schedule, once the admin has set one
+    #[account(seeds = [b"tou_schedule"], bump = schedule.bump)]
+    pub schedule: Option<Account<'info, MultiplierSchedule>>,
+
+    /// CHECK: Demand-response slot of the station's grid region - validated by seeds, read only if an
+    /// event has been published there. Required so a driver cannot pick which event applies
+    #[account(
+        seeds = [b"demand_response".as_ref(), &session.grid_region.to_le_bytes()],
+        bump
+    )]
+    pub demand_response: UncheckedAccount<'info>,
+
+    /// CHECK: Session driver - validated by has_one
+    pub user: AccountInfo<'info>,
+
+    /// The driver or a session delegate
+    pub authority: Signer<'info>,
+
+    /// CHECK: Instructions sysvar - used to read the Ed25519 meter signature instruction
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EndSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    #[account(
+        mut,
+        seeds = [b"user", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    /// Only required for prepaid sessions
+    #[account(
+        mut,
+        seeds = [b"escrow", session.key().as_ref()],
+        bump = escrow.bump,
+        close = user
+    )]
+    pub escrow: Option<Account<'info, SessionEscrow>>,
+
+    /// CHECK: Station operator receiving payment - validated against escrow.operator
+    #[account(mut)]
+    pub operator: Option<AccountInfo<'info>>,
+
+    /// CHECK: Carbon intensity slot of the station's grid region - validated by seeds, read only once
+    /// an intensity has been published there. Required so a driver cannot skip the carbon settlement
+    #[account(
+        seeds = [b"carbon_intensity".as_ref(), &session.grid_region.to_le_bytes()],
+        bump
+    )]
+    pub carbon_intensity: UncheckedAccount<'info>,
+
+    /// Required while the driver's referrer is still being rewarded for their sessions
+    #[account(
+        mut,
+        seeds = [b"user", referrer_account.authority.as_ref()],
+        bump = referrer_account.bump
+    )]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    /// The referrer's associated points account, so it is only accepted with the referrer account
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = referrer_account.as_ref().map_or(Pubkey::default(), |referrer| referrer.authority),
+        associated_token::token_program = token_program
+    )]
+    pub referrer_points_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Required when the session was started by a fleet member
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump
+    )]
+    pub fleet: Option<Box<Account<'info, Fleet>>>,
+
+    /// Required while the driver is still a member of that fleet
+    #[account(
+        mut,
+        seeds = [b"fleet_member", fleet_member.fleet.as_ref(), fleet_member.member.as_ref()],
+        bump = fleet_member.bump
+    )]
+    pub fleet_member: Option<Box<Account<'info, FleetMember>>>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = fleet,
+        associated_token::token_program = token_program
+    )]
+    pub fleet_points_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Current season, required once the first season has started
+    #[account(
+        mut,
+        seeds = [SEASON_SEED, &season.season_id.to_le_bytes()],
+        bump = season.bump,
+        constraint = season.season_id == config.current_season @ ErrorCode::SeasonNotCurrent
+    )]
+    pub season: Option<Box<Account<'info, Season>>>,
+
+    /// Required once the current season is over, which the session then closes
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Season::INIT_SPACE,
+        seeds = [SEASON_SEED, &(config.current_season + 1).to_le_bytes()],
+        bump
+    )]
+    pub next_season: Option<Box<Account<'info, Season>>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Session driver receiving refunds - validated by has_one
+    #[account(mut)]
+    pub user: AccountInfo<'info>,
+
+    /// The driver or a session delegate; pays the next season's rent when the session starts it
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUser<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user", authority.key().as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = points_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The referring driver, if any
+    #[account(
+        mut,
+        seeds = [b"user", referrer_account.authority.as_ref()],
+        bump = referrer_account.bump
+    )]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModifyPoints<'info> {
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = user_account.authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"authorized_caller", authorized_caller.program_id.as_ref()],
+        bump = authorized_caller.bump,
+        constraint = authorized_caller.authority == caller_authority.key() @ ErrorCode::UnauthorizedCaller
+    )]
+    pub authorized_caller: Account<'info, AuthorizedCaller>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    /// PDA of the calling program - must match the authorized caller registration
+    /// The calling program signs with this PDA to prove its identity
+    pub caller_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemVoucher<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    // init_if_needed so a second attempt reaches the redeemed checks and fails with
+    // VoucherAlreadyRedeemed instead of a bare system program error
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VoucherRedemption::INIT_SPACE,
+        seeds = [b"redemption", voucher.key().as_ref()],
+        bump
+    )]
+    pub redemption_record: Account<'info, VoucherRedemption>,
+
+    /// CHECK: Marketplace voucher - owner checked here, contents deserialized in the instruction
+    #[account(mut, owner = points_marketplace::ID @ ErrorCode::InvalidVoucherProgram)]
+    pub voucher: AccountInfo<'info>,
+
+    /// CHECK: PDA this program signs mark_voucher_redeemed with - validated by seeds constraint
+    #[account(seeds = [VOUCHER_REDEEMER_SEED], bump)]
+    pub voucher_redeemer: AccountInfo<'info>,
+
+    pub marketplace_program: Program<'info, PointsMarketplace>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePointsMint<'info> {
+    /// CHECK: Created and initialized as a Token-2022 mint in the instruction - validated by seeds
+    /// Anchor's mint constraints cannot add the non-transferable extension
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: UncheckedAccount<'info>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::ChargingSession>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::UnauthorizedAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePoints<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = points_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct RegisterStation<'info> {
+    #[account(
+        init,
+        payer = operator,
+        space = 8 + ChargerStation::INIT_SPACE,
+        seeds = [b"station", code.as_bytes()],
+        bump
+    )]
+    pub station: Account<'info, ChargerStation>,
+
+    #[account(mut)]
+    pub operator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStation<'info> {
+    #[account(
+        mut,
+        seeds = [b"station", station.code.as_bytes()],
+        bump = station.bump,
+        has_one = operator
+    )]
+    pub station: Account<'info, ChargerStation>,
+
+    pub operator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(meter: Pubkey)]
+pub struct RegisterMeter<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + MeterRegistration::INIT_SPACE,
+        seeds = [b"meter", meter.as_ref()],
+        bump
+    )]
+    pub meter_registration: Account<'info, MeterRegistration>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMeterStatus<'info> {
+    #[account(
+        mut,
+        seeds = [b"meter", meter_registration.meter.as_ref()],
+        bump = meter_registration.bump
+    )]
+    pub meter_registration: Account<'info, MeterRegistration>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user,
+        close = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + SessionHistory::INIT_SPACE,
+        seeds = [b"history", user.key().as_ref()],
+        bump
+    )]
+    pub history: Box<Account<'info, SessionHistory>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireSession<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user,
+        close = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    #[account(
+        mut,
+        seeds = [b"user", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    /// Only required for prepaid sessions
+    #[account(
+        mut,
+        seeds = [b"escrow", session.key().as_ref()],
+        bump = escrow.bump,
+        close = user
+    )]
+    pub escrow: Option<Account<'info, SessionEscrow>>,
+
+    /// CHECK: Station operator receiving payment - validated against escrow.operator
+    #[account(mut)]
+    pub operator: Option<AccountInfo<'info>>,
+
+    /// CHECK: Carbon intensity slot of the station's grid region - validated by seeds, read only once
+    /// an intensity has been published there. Required so a driver cannot skip the carbon settlement
+    #[account(
+        seeds = [b"carbon_intensity".as_ref(), &session.grid_region.to_le_bytes()],
+        bump
+    )]
+    pub carbon_intensity: UncheckedAccount<'info>,
+
+    /// Required while the driver's referrer is still being rewarded for their sessions
+    #[account(
+        mut,
+        seeds = [b"user", referrer_account.authority.as_ref()],
+        bump = referrer_account.bump
+    )]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    /// The referrer's associated points account, so it is only accepted with the referrer account
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = referrer_account.as_ref().map_or(Pubkey::default(), |referrer| referrer.authority),
+        associated_token::token_program = token_program
+    )]
+    pub referrer_points_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Required when the session was started by a fleet member
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump
+    )]
+    pub fleet: Option<Box<Account<'info, Fleet>>>,
+
+    /// Required while the driver is still a member of that fleet
+    #[account(
+        mut,
+        seeds = [b"fleet_member", fleet_member.fleet.as_ref(), fleet_member.member.as_ref()],
+        bump = fleet_member.bump
+    )]
+    pub fleet_member: Option<Box<Account<'info, FleetMember>>>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = fleet,
+        associated_token::token_program = token_program
+    )]
+    pub fleet_points_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// Current season, required once the first season has started
+    #[account(
+        mut,
+        seeds = [SEASON_SEED, &season.season_id.to_le_bytes()],
+        bump = season.bump,
+        constraint = season.season_id == config.current_season @ ErrorCode::SeasonNotCurrent
+    )]
+    pub season: Option<Box<Account<'info, Season>>>,
+
+    /// Required once the current season is over, which the session then closes
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + Season::INIT_SPACE,
+        seeds = [SEASON_SEED, &(config.current_season + 1).to_le_bytes()],
+        bump
+    )]
+    pub next_season: Option<Box<Account<'info, Season>>>,
+
+    /// Archived into when the driver already has a history account
+    #[account(
+        mut,
+        seeds = [b"history", user.key().as_ref()],
+        bump = history.bump
+    )]
+    pub history: Option<Box<Account<'info, SessionHistory>>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Session driver receiving points, refunds and the remaining rent - validated by has_one
+    #[account(mut)]
+    pub user: AccountInfo<'info>,
+
+    /// Anyone may crank an expired session
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocolConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ProtocolConfig::INIT_SPACE,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::ChargingSession>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::UnauthorizedAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetLoyaltyBenefits<'info> {
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [b"user", user_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetTouSchedule<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + MultiplierSchedule::INIT_SPACE,
+        seeds = [b"tou_schedule"],
+        bump
+    )]
+    pub schedule: Account<'info, MultiplierSchedule>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct RegisterGridOperator<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GridOperator::INIT_SPACE,
+        seeds = [b"grid_operator", authority.as_ref()],
+        bump
+    )]
+    pub grid_operator: Account<'info, GridOperator>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveGridOperator<'info> {
+    #[account(
+        mut,
+        seeds = [b"grid_operator", grid_operator.authority.as_ref()],
+        bump = grid_operator.bump,
+        close = admin
+    )]
+    pub grid_operator: Account<'info, GridOperator>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PublishDemandResponse<'info> {
+    #[account(
+        seeds = [b"grid_operator", authority.key().as_ref()],
+        bump = grid_operator.bump
+    )]
+    pub grid_operator: Account<'info, GridOperator>,
+
+    /// The region's event slot, reused across events
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + DemandResponseEvent::INIT_SPACE,
+        seeds = [b"demand_response".as_ref(), &grid_operator.grid_region.to_le_bytes()],
+        bump
+    )]
+    pub event: Account<'info, DemandResponseEvent>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelDemandResponse<'info> {
+    #[account(
+        mut,
+        seeds = [b"demand_response".as_ref(), &event.grid_region.to_le_bytes()],
+        bump = event.bump,
+        constraint = event.grid_operator == authority.key() @ ErrorCode::UnauthorizedGridOperator,
+        close = authority
+    )]
+    pub event: Account<'info, DemandResponseEvent>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(region: u16)]
+pub struct SetCarbonIntensity<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + CarbonIntensity::INIT_SPACE,
+        seeds = [b"carbon_intensity".as_ref(), &region.to_le_bytes()],
+        bump
+    )]
+    pub carbon_intensity: Account<'info, CarbonIntensity>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        constraint = authority.key() == config.admin || authority.key() == config.carbon_oracle
+            @ ErrorCode::UnauthorizedCarbonOracle
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    /// The protocol admin or the carbon oracle
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct RegisterAuthorizedCaller<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AuthorizedCaller::INIT_SPACE,
+        seeds = [b"authorized_caller", program_id.as_ref()],
+        bump
+    )]
+    pub authorized_caller: Account<'info, AuthorizedCaller>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAuthorizedCaller<'info> {
+    #[account(
+        mut,
+        seeds = [b"authorized_caller", authorized_caller.program_id.as_ref()],
+        bump = authorized_caller.bump
+    )]
+    pub authorized_caller: Account<'info, AuthorizedCaller>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAuthorizedCaller<'info> {
+    #[account(
+        mut,
+        seeds = [b"authorized_caller", authorized_caller.program_id.as_ref()],
+        bump = authorized_caller.bump,
+        close = admin
+    )]
+    pub authorized_caller: Account<'info, AuthorizedCaller>,
+
+    #[account(
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ManageSessionDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SessionControl<'info> {
+    #[account(
+        mut,
+        seeds = [b"session", session.user.as_ref(), &session.seed_timestamp.to_le_bytes(), &session.nonce.to_le_bytes()],
+        bump = session.bump,
+        has_one = user
+    )]
+    pub session: Account<'info, ChargingSession>,
+
+    /// CHECK: Session driver - validated by has_one
+    pub user: AccountInfo<'info>,
+
+    /// The driver or a session delegate
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuyGraceDay<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, ProtocolConfig>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ExpirePoints<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", user_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = user_account.authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct TransferPoints<'info> {
+    #[account(
+        mut,
+        seeds = [b"user", sender.key().as_ref()],
+        bump = sender_account.bump
+    )]
+    pub sender_account: Account<'info, UserAccount>,
+
+    /// Must already be registered; points cannot be sent to arbitrary wallets
+    #[account(
+        mut,
+        seeds = [b"user", recipient_account.authority.as_ref()],
+        bump = recipient_account.bump,
+        constraint = recipient_account.authority != sender.key() @ ErrorCode::InvalidTransferRecipient
+    )]
+    pub recipient_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = sender,
+        associated_token::token_program = token_program
+    )]
+    pub sender_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = recipient_account.authority,
+        associated_token::token_program = token_program
+    )]
+    pub recipient_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub sender: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+#[instruction(fleet_id: u64)]
+pub struct CreateFleet<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Fleet::INIT_SPACE,
+        seeds = [FLEET_SEED, owner.key().as_ref(), &fleet_id.to_le_bytes()],
+        bump
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Fleet points pool
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = points_mint,
+        associated_token::authority = fleet,
+        associated_token::token_program = token_program
+    )]
+    pub fleet_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageFleetAdmins<'info> {
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, owner.key().as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump,
+        has_one = owner @ ErrorCode::UnauthorizedFleetAdmin
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddFleetMember<'info> {
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump,
+        constraint = fleet.is_admin(&admin.key()) @ ErrorCode::UnauthorizedFleetAdmin
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + FleetMember::INIT_SPACE,
+        seeds = [b"fleet_member", fleet.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub fleet_member: Account<'info, FleetMember>,
+
+    #[account(
+        mut,
+        seeds = [b"user", member.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    pub member: Signer<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetFleetMemberLimit<'info> {
+    #[account(
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump,
+        constraint = fleet.is_admin(&admin.key()) @ ErrorCode::UnauthorizedFleetAdmin
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(
+        mut,
+        seeds = [b"fleet_member", fleet.key().as_ref(), fleet_member.member.as_ref()],
+        bump = fleet_member.bump
+    )]
+    pub fleet_member: Account<'info, FleetMember>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFleetMember<'info> {
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump,
+        has_one = owner @ ErrorCode::InvalidFleetAccount,
+        constraint = fleet.is_admin(&authority.key()) || fleet_member.member == authority.key()
+            @ ErrorCode::UnauthorizedFleetAdmin
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(
+        mut,
+        seeds = [b"fleet_member", fleet.key().as_ref(), fleet_member.member.as_ref()],
+        bump = fleet_member.bump,
+        close = owner
+    )]
+    pub fleet_member: Account<'info, FleetMember>,
+
+    #[account(
+        mut,
+        seeds = [b"user", fleet_member.member.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Fleet owner, receives the member account's rent
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFleetPoints<'info> {
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(
+        mut,
+        seeds = [b"fleet_member", fleet.key().as_ref(), member.key().as_ref()],
+        bump = fleet_member.bump
+    )]
+    pub fleet_member: Account<'info, FleetMember>,
+
+    #[account(
+        mut,
+        seeds = [b"user", member.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = fleet,
+        associated_token::token_program = token_program
+    )]
+    pub fleet_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = member,
+        associated_token::token_program = token_program
+    )]
+    pub member_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub member: Signer<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireFleetPoints<'info> {
+    #[account(
+        mut,
+        seeds = [FLEET_SEED, fleet.owner.as_ref(), &fleet.fleet_id.to_le_bytes()],
+        bump = fleet.bump
+    )]
+    pub fleet: Account<'info, Fleet>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = fleet,
+        associated_token::token_program = token_program
+    )]
+    pub fleet_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct StartSeason<'info> {
+    #[account(
+        mut,
+        seeds = [PROTOCOL_CONFIG_SEED],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::UnauthorizedAdmin
+    )]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Season::INIT_SPACE,
+        seeds = [SEASON_SEED, &(config.current_season + 1).to_le_bytes()],
+        bump
+    )]
+    pub season: Box<Account<'info, Season>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSeason<'info> {
+    #[account(mut, seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(
+        mut,
+        seeds = [SEASON_SEED, &config.current_season.to_le_bytes()],
+        bump = season.bump
+    )]
+    pub season: Box<Account<'info, Season>>,
+
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + Season::INIT_SPACE,
+        seeds = [SEASON_SEED, &(config.current_season + 1).to_le_bytes()],
+        bump
+    )]
+    pub next_season: Box<Account<'info, Season>>,
+
+    /// Anyone can close a finished season; pays the next season's rent
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DistributeSeasonPrize<'info> {
+    #[account(
+        mut,
+        seeds = [SEASON_SEED, &season.season_id.to_le_bytes()],
+        bump = season.bump
+    )]
+    pub season: Box<Account<'info, Season>>,
+
+    #[account(
+        mut,
+        seeds = [b"user", user_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Box<Account<'info, UserAccount>>,
+
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, ProtocolConfig>>,
+
+    #[account(mut, seeds = [POINTS_MINT_SEED], bump)]
+    pub points_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Points mint authority PDA - validated by seeds constraint
+    #[account(seeds = [POINTS_AUTHORITY_SEED], bump)]
+    pub points_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = points_mint,
+        associated_token::authority = user_account.authority,
+        associated_token::token_program = token_program
+    )]
+    pub user_points_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeUserAccount<'info> {
+    /// CHECK: May not decode with the current layout yet - owner and discriminator checked by grow_account
+    #[account(mut)]
+    pub user_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeSession<'info> {
+    /// CHECK: May not decode with the current layout yet - owner and discriminator checked by grow_account,
+    /// seeds checked once grown
+    #[account(mut)]
+    pub session: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeProtocolConfig<'info> {
+    /// CHECK: May not decode with the current layout yet - owner and discriminator checked by grow_account,
+    /// admin checked once grown
+    #[account(mut, seeds = [PROTOCOL_CONFIG_SEED], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ChargingSession {
+    pub user: Pubkey,
+    #[max_len(20)]
+    pub charger_code: String,
+    pub charger_power_kw: u16,
+    pub pricing_per_kwh: u64,
+    pub start_time: i64,
+    pub nonce: u32,
+    pub end_time: Option<i64>,
+    pub energy_consumed_wh: u64, // in watt-hours
+    pub points_earned: u64,      // whole points, the sum of the milli-point buckets / 1000
+    pub is_active: bool,
+    pub bump: u8,
+    // Fields added since launch; new fields go at the end
+    pub meter: Pubkey,
+    pub last_reading_sequence: u64,
+    pub station: Pubkey,
+    pub energy_tolerance_pct: u8,
+    pub last_update_time: i64,
+    pub anomaly_count: u32, // implausible readings rejected
+    pub last_anomaly_time: Option<i64>,
+    pub deposit_lamports: u64,     // 0 when the session is not prepaid
+    pub amount_paid_lamports: u64, // settled to the operator at end_session
+    pub deposit_exhausted: bool,
+    pub max_duration_secs: u32,
+    pub idle_timeout_secs: u32,
+    pub points_per_kwh: u64,
+    pub base_milli_points: u64,       // milli-points at 1x
+    pub tou_bonus_milli_points: u64,  // extra milli-points from time-of-use multipliers
+    pub dr_bonus_milli_points: u64,   // extra milli-points from demand-response events
+    pub delegate: Option<Pubkey>, // charger device key acting for the driver
+    pub delegate_scope: u8,       // DELEGATE_SCOPE_* flags
+    pub delegate_expires_at: i64,
+    pub idle_fee_per_minute_lamports: u64,
+    pub idle_grace_period_secs: u32,
+    pub paused_at: Option<i64>,    // set while the session is paused
+    pub total_paused_secs: u64,    // completed pauses only
+    pub idle_fee_lamports: u64,    // computed at end_session; collected from the deposit (up to its size) only for prepaid sessions
+    pub mode: SessionMode,
+    pub export_points_per_kwh: u64, // reward rate for Wh exported in discharge sessions
+    pub energy_exported_wh: u64, // delivered to the grid, discharge sessions only
+    pub export_milli_points: u64,     // milli-points at 1x for energy exported to the grid
+    pub grid_region: u16,
+    pub ice_baseline_g_co2_per_kwh: u32,
+    pub low_carbon_threshold_g_co2_per_kwh: u32,
+    pub low_carbon_points_per_kwh: u64,
+    pub grid_carbon_g_co2_per_kwh: Option<u32>, // region intensity at end_session, if published
+    pub co2_avoided_g: u64,      // versus the ICE baseline, for the energy consumed
+    pub carbon_bonus_milli_points: u64, // low-carbon charging bonus, awarded at end_session
+    pub loyalty_bonus_milli_points: u64, // driver's loyalty tier multiplier, applied at end_session
+    pub achievement_bonus_points: u64,  // one-time bonuses for achievements unlocked by this session
+    pub streak_bonus_points: u64,       // bonus for a streak milestone reached by this session
+    pub seed_timestamp: i64,            // driver-chosen PDA seed; start_time is the clock time at start
+    pub fleet: Option<Pubkey>,          // driver's fleet at start_session, whose pool receives the points
+    pub min_duration_secs: u32,         // end_session is refused until the session is this old
+    pub last_energy_time: i64,          // last reading reporting energy, applied or not, or resume; idle time counts from here
+}
+
+impl ChargingSession {
+    /// Require `authority` to be the driver, or an unexpired delegate holding `scope`
+    pub fn check_authority(&self, authority: &Pubkey, scope: u8, now: i64) -> Result<()> {
+        if *authority == self.user {
+            return Ok(());
+        }
+        require!(
+            self.delegate == Some(*authority)
+                && self.delegate_scope & scope == scope
+                && now < self.delegate_expires_at,
+            ErrorCode::UnauthorizedDelegate
+        );
+        Ok(())
+    }
+
+    /// Credit milli-points for `accrued_wh` at the session rate scaled by both multipliers,
+    /// recording how much of the award came from each multiplier
+    /// Accrual is kept in milli-points and only converted to whole points on the session total,
+    /// so the points earned do not depend on how the meter readings were batched
+    pub fn accrue_points(&mut self, accrued_wh: u64, tou_bps: u16, dr_bps: u16) -> Result<()> {
+        // points_per_kwh points per 1000 Wh is exactly points_per_kwh milli-points per Wh
+        let base = accrued_wh as u128 * self.points_per_kwh as u128;
+        let bps = MULTIPLIER_BPS_BASE as u128;
+
+        let with_tou = base * tou_bps as u128 / bps;
+        let total = base * tou_bps as u128 * dr_bps as u128 / (bps * bps);
+
+        let to_u64 = |value: u128| u64::try_from(value).map_err(|_| error!(ErrorCode::Overflow));
+
+        self.base_milli_points = self.base_milli_points
+            .checked_add(to_u64(base)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.tou_bonus_milli_points = self.tou_bonus_milli_points
+            .checked_add(to_u64(with_tou - base)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.dr_bonus_milli_points = self.dr_bonus_milli_points
+            .checked_add(to_u64(total - with_tou)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.points_earned = self.earned_milli_points()? / 1000;
+        Ok(())
+    }
+
+    /// Credit milli-points for `exported_wh` at the session export rate scaled by the
+    /// demand-response multiplier, which is recorded with the other demand-response bonuses
+    pub fn accrue_export_points(&mut self, exported_wh: u64, dr_bps: u16) -> Result<()> {
+        let base = exported_wh as u128 * self.export_points_per_kwh as u128;
+        let total = base * dr_bps as u128 / MULTIPLIER_BPS_BASE as u128;
+
+        let to_u64 = |value: u128| u64::try_from(value).map_err(|_| error!(ErrorCode::Overflow));
+
+        self.export_milli_points = self.export_milli_points
+            .checked_add(to_u64(base)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.dr_bonus_milli_points = self.dr_bonus_milli_points
+            .checked_add(to_u64(total - base)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.points_earned = self.earned_milli_points()? / 1000;
+        Ok(())
+    }
+
+    pub fn earned_milli_points(&self) -> Result<u64> {
+        self.base_milli_points
+            .checked_add(self.tou_bonus_milli_points)
+            .and_then(|sum| sum.checked_add(self.dr_bonus_milli_points))
+            .and_then(|sum| sum.checked_add(self.export_milli_points))
+            .and_then(|sum| sum.checked_add(self.carbon_bonus_milli_points))
+            .and_then(|sum| sum.checked_add(self.loyalty_bonus_milli_points))
+            .ok_or(error!(ErrorCode::Overflow))
+    }
+
+    /// Session points plus the bonuses for any achievements or streak milestone it reached
+    pub fn points_to_mint(&self) -> Result<u64> {
+        self.points_earned
+            .checked_add(self.achievement_bonus_points)
+            .and_then(|total| total.checked_add(self.streak_bonus_points))
+            .ok_or(error!(ErrorCode::Overflow))
+    }
+
+    /// Scale everything earned so far by the driver's loyalty multiplier
+    pub fn apply_loyalty_multiplier(&mut self, multiplier_bps: u16) -> Result<()> {
+        let earned = self.earned_milli_points()? as u128;
+        let bonus = earned * multiplier_bps.saturating_sub(MULTIPLIER_BPS_BASE) as u128
+            / MULTIPLIER_BPS_BASE as u128;
+
+        self.loyalty_bonus_milli_points = self.loyalty_bonus_milli_points
+            .checked_add(u64::try_from(bonus).map_err(|_| ErrorCode::Overflow)?)
+            .ok_or(ErrorCode::Overflow)?;
+        self.points_earned = self.earned_milli_points()? / 1000;
+        Ok(())
+    }
+
+    /// Record the grid intensity the session charged at, the emissions avoided against the
+    /// ICE baseline and, if the grid was below the low-carbon threshold, the bonus points
+    pub fn settle_carbon(&mut self, g_co2_per_kwh: u32) -> Result<()> {
+        let saved_g_per_kwh = self.ice_baseline_g_co2_per_kwh.saturating_sub(g_co2_per_kwh);
+        let co2_avoided_g = self.energy_consumed_wh as u128 * saved_g_per_kwh as u128 / 1000;
+
+        self.grid_carbon_g_co2_per_kwh = Some(g_co2_per_kwh);
+        self.co2_avoided_g = u64::try_from(co2_avoided_g).map_err(|_| ErrorCode::Overflow)?;
+
+        if g_co2_per_kwh <= self.low_carbon_threshold_g_co2_per_kwh {
+            self.carbon_bonus_milli_points = self.energy_consumed_wh
+                .checked_mul(self.low_carbon_points_per_kwh)
+                .ok_or(ErrorCode::Overflow)?;
+            self.points_earned = self.earned_milli_points()? / 1000;
+        }
+        Ok(())
+    }
+
+    /// Whether any energy was charged or exported, which a session needs to count towards rewards
+    pub fn delivered_energy(&self) -> bool {
+        self.energy_consumed_wh > 0 || self.energy_exported_wh > 0
+    }
+
+    /// Whether the session has run past its max duration or gone idle too long
+    pub fn is_expired(&self, now: i64) -> bool {
+        let max_end = self.start_time.saturating_add(self.max_duration_secs as i64);
+        let idle_end = self.last_energy_time.saturating_add(self.idle_timeout_secs as i64);
+        now >= max_end || now >= idle_end
+    }
+
+    /// Energy (Wh) the deposit can pay for, or None if accrual is not limited by a deposit
+    pub fn prepaid_energy_wh(&self) -> Option<u64> {
+        if self.deposit_lamports == 0 || self.pricing_per_kwh == 0 {
+            return None;
+        }
+        let prepaid_wh = self.deposit_lamports as u128 * 1000 / self.pricing_per_kwh as u128;
+        Some(u64::try_from(prepaid_wh).unwrap_or(u64::MAX))
+    }
+
+    /// Cost of the energy consumed so far plus any idle fee, in lamports
+    pub fn amount_due_lamports(&self) -> Result<u64> {
+        let energy_cost = self.energy_consumed_wh as u128 * self.pricing_per_kwh as u128 / 1000;
+        let amount_due = energy_cost + self.idle_fee_lamports as u128;
+        Ok(u64::try_from(amount_due).map_err(|_| ErrorCode::Overflow)?)
+    }
+
+    /// Close the current pause, if any, adding its length to the total paused time
+    pub fn end_pause(&mut self, now: i64) -> Result<()> {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused_secs = now.saturating_sub(paused_at).max(0) as u64;
+            self.total_paused_secs = self.total_paused_secs
+                .checked_add(paused_secs)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Idle fee for the whole minutes the session sat without drawing energy beyond the grace period:
+    /// completed pauses plus the time since the meter last reported energy (or resume), so a car left
+    /// plugged in after charging is billed whether or not the driver paused the session
+    pub fn idle_fee_due_lamports(&self, now: i64) -> Result<u64> {
+        let idle_secs = self.total_paused_secs
+            .checked_add(now.saturating_sub(self.last_energy_time).max(0) as u64)
+            .ok_or(ErrorCode::Overflow)?;
+        let idle_minutes = idle_secs
+            .saturating_sub(self.idle_grace_period_secs as u64) / 60;
+        idle_minutes
+            .checked_mul(self.idle_fee_per_minute_lamports)
+            .ok_or(error!(ErrorCode::Overflow))
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SessionEscrow {
+    pub session: Pubkey,
+    pub user: Pubkey,
+    pub operator: Pubkey,
+    pub deposit_lamports: u64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UserAccount {
+    pub authority: Pubkey,
+    pub total_points: u64,     // lifetime points earned
+    pub available_points: u64, // sum of point_lots, plus any legacy balance not yet migrated with migrate_points
+    pub total_energy_kwh: u64,
+    pub total_sessions: u64,
+    pub bump: u8,
+    // Fields added since launch. New fields go at the end so existing accounts can be
+    // grown with upgrade_user_account
+    pub energy_remainder_wh: u16, // energy not yet counted in total_energy_kwh, always < 1000
+    pub total_exported_wh: u64,   // lifetime energy exported to the grid, not part of total_energy_kwh
+    pub total_co2_avoided_g: u64, // lifetime emissions avoided versus the ICE baseline
+    pub loyalty_tier: u8,         // 0 = base level, n = ProtocolConfig::loyalty_tiers[n - 1]
+    pub achievements: u32,        // ACHIEVEMENT_* flags unlocked so far
+    pub current_streak_days: u16, // consecutive UTC days with a completed session
+    pub longest_streak_days: u16,
+    pub last_session_day: i64,    // UTC day number of the last completed session
+    pub grace_days: u8,           // bought with points, each covers one missed day
+    pub referrer: Option<Pubkey>,    // authority of the driver who referred this one
+    pub referral_sessions: u16,      // sessions that have paid the referrer
+    pub referral_energy_wh: u64,     // energy of those sessions
+    pub referral_points_paid: u64,   // points paid to the referrer for this driver
+    pub referral_count: u32,         // drivers this one has referred
+    pub referral_points_earned: u64, // from referred drivers, included in total_points
+    #[max_len(12)]
+    pub point_lots: Vec<PointLot>,   // oldest first, spent FIFO
+    pub expired_points: u64,         // lifetime points burned by expire_points
+    pub transferred_today: u64,      // points sent with transfer_points on transfer_day
+    pub transfer_day: i64,           // days since the unix epoch that transferred_today refers to
+    pub fleet: Option<Pubkey>,       // fleet whose pool receives this driver's session points
+    pub season_id: u32,              // season that season_energy_wh and season_points refer to
+    pub season_energy_wh: u64,
+    pub season_points: u64,          // session points earned in that season, excluding one-off bonuses
+}
+
+impl UserAccount {
+    /// Record newly minted points as a lot expiring `lifetime_secs` from now, or never when 0
+    /// Once every slot is used the two oldest lots are merged into the earlier of their expiries,
+    /// so no points outlive the lifetime they were credited with
+    pub fn credit_lot(&mut self, amount: u64, now: i64, lifetime_secs: u32) -> Result<()> {
+        self.add_lot(amount, now, lot_expiry(now, lifetime_secs))
+    }
+
+    /// Record points credited at `now` that expire at `expires_at`
+    pub fn add_lot(&mut self, amount: u64, now: i64, expires_at: i64) -> Result<()> {
+        push_lot(&mut self.point_lots, amount, now, expires_at)?;
+        self.available_points = self.available_points
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Points held in lots, i.e. the driver's token balance
+    pub fn tracked_points(&self) -> u64 {
+        lots_total(&self.point_lots)
+    }
+
+    /// Spend points from the oldest lots first, returning what was taken from each lot
+    pub fn debit_lots(&mut self, amount: u64) -> Vec<PointLot> {
+        let spent = spend_lots(&mut self.point_lots, amount);
+        self.available_points = self.available_points.saturating_sub(lots_total(&spent));
+        spent
+    }
+
+    /// Count a transfer against the daily limit, resetting the counter on a new UTC day
+    pub fn record_transfer(&mut self, amount: u64, daily_limit: u64, now: i64) -> Result<()> {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        if today != self.transfer_day {
+            self.transfer_day = today;
+            self.transferred_today = 0;
+        }
+
+        let transferred = self.transferred_today
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(daily_limit > 0, ErrorCode::TransfersDisabled);
+        require!(transferred <= daily_limit, ErrorCode::DailyTransferLimitExceeded);
+
+        self.transferred_today = transferred;
+        Ok(())
+    }
+
+    /// Remove the lots that have expired by `now`, returning their points
+    pub fn take_expired_lots(&mut self, now: i64) -> u64 {
+        let expired = take_expired_lots(&mut self.point_lots, now);
+        self.available_points = self.available_points.saturating_sub(expired);
+        expired
+    }
+
+    /// Extend or restart the daily streak with a session completed at `end_time`, spending
+    /// banked grace days to cover missed days; returns whether the streak gained a day
+    pub fn record_session_day(&mut self, end_time: i64) -> Result<bool> {
+        let day = end_time.div_euclid(SECONDS_PER_DAY);
+        if self.current_streak_days > 0 && day <= self.last_session_day {
+            return Ok(false);
+        }
+
+        let missed_days = day - self.last_session_day - 1;
+        if self.current_streak_days > 0 && missed_days <= self.grace_days as i64 {
+            self.grace_days -= missed_days as u8;
+            self.current_streak_days = self.current_streak_days
+                .checked_add(1)
+                .ok_or(ErrorCode::Overflow)?;
+        } else {
+            self.current_streak_days = 1;
+        }
+        self.longest_streak_days = self.longest_streak_days.max(self.current_streak_days);
+        self.last_session_day = day;
+        Ok(true)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PointLot {
+    pub amount: u64,      // points remaining in the lot
+    pub credited_at: i64, // first credit; later credits that UTC day are added to the lot
+    pub expires_at: i64,  // i64::MAX when points do not expire
+}
+
+/// Expiry of points credited at `now` with the given lifetime, never when 0
+pub fn lot_expiry(now: i64, lifetime_secs: u32) -> i64 {
+    if lifetime_secs == 0 {
+        i64::MAX
+    } else {
+        now.saturating_add(lifetime_secs as i64)
+    }
+}
+
+pub fn lots_total(lots: &[PointLot]) -> u64 {
+    lots.iter().fold(0u64, |sum, lot| sum.saturating_add(lot.amount))
+}
+
+/// Add points credited at `now` that expire at `expires_at` to a list of lots, oldest first
+/// Joins the newest lot when both were credited and expire on the same UTC days; once every
+/// slot is used the two oldest lots are merged into the older lot's credit time and the earlier
+/// of their expiries, so merged points may expire early but never late
+pub fn push_lot(lots: &mut Vec<PointLot>, amount: u64, now: i64, expires_at: i64) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    if let Some(lot) = lots.last_mut() {
+        if lot.credited_at.div_euclid(SECONDS_PER_DAY) == now.div_euclid(SECONDS_PER_DAY)
+            && lot.expires_at.div_euclid(SECONDS_PER_DAY) == expires_at.div_euclid(SECONDS_PER_DAY)
+        {
+            lot.amount = lot.amount.checked_add(amount).ok_or(ErrorCode::Overflow)?;
+            lot.expires_at = lot.expires_at.max(expires_at);
+            return Ok(());
+        }
+    }
+
+    if lots.len() == MAX_POINT_LOTS {
+        let oldest = lots.remove(0);
+        lots[0].amount = lots[0].amount
+            .checked_add(oldest.amount)
+            .ok_or(ErrorCode::Overflow)?;
+        lots[0].credited_at = oldest.credited_at;
+        lots[0].expires_at = lots[0].expires_at.min(oldest.expires_at);
+    }
+    lots.push(PointLot { amount, credited_at: now, expires_at });
+    Ok(())
+}
+
+/// Take up to `amount` points from the oldest lots first
+/// Returns the part taken from each lot, with that lot's credit time and expiry
+pub fn spend_lots(lots: &mut Vec<PointLot>, mut amount: u64) -> Vec<PointLot> {
+    let mut spent = Vec::new();
+    while amount > 0 {
+        let Some(lot) = lots.first_mut() else {
+            break;
+        };
+        let taken = lot.amount.min(amount);
+        lot.amount -= taken;
+        amount -= taken;
+        spent.push(PointLot { amount: taken, ..lot.clone() });
+        if lot.amount == 0 {
+            lots.remove(0);
+        }
+    }
+    spent
+}
+
+/// Remove the lots that have expired by `now`, returning their points
+pub fn take_expired_lots(lots: &mut Vec<PointLot>, now: i64) -> u64 {
+    let mut expired: u64 = 0;
+    lots.retain(|lot| {
+        if lot.expires_at > now {
+            return true;
+        }
+        expired = expired.saturating_add(lot.amount);
+        false
+    });
+    expired
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ChargerStation {
+    pub operator: Pubkey,
+    #[max_len(20)]
+    pub code: String,
+    #[max_len(6)]
+    pub connector_types: Vec<ConnectorType>,
+    pub charger_power_kw: u16,
+    pub pricing_per_kwh: u64, // in lamports
+    pub energy_tolerance_pct: u8,
+    pub max_session_duration_secs: u32,
+    pub idle_timeout_secs: u32,
+    pub idle_fee_per_minute_lamports: u64,
+    pub idle_grace_period_secs: u32,
+    pub supports_v2g: bool,
+    pub grid_region: u16,
+    pub latitude: i32,
+    pub longitude: i32,
+    pub is_active: bool,
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+impl ChargerStation {
+    fn apply(&mut self, params: StationParams) {
+        self.connector_types = params.connector_types;
+        self.charger_power_kw = params.charger_power_kw;
+        self.pricing_per_kwh = params.pricing_per_kwh;
+        self.energy_tolerance_pct = params.energy_tolerance_pct;
+        self.max_session_duration_secs = params.max_session_duration_secs;
+        self.idle_timeout_secs = params.idle_timeout_secs;
+        self.idle_fee_per_minute_lamports = params.idle_fee_per_minute_lamports;
+        self.idle_grace_period_secs = params.idle_grace_period_secs;
+        self.supports_v2g = params.supports_v2g;
+        self.grid_region = params.grid_region;
+        self.latitude = params.latitude;
+        self.longitude = params.longitude;
+    }
+}
+
+/// Operator-controlled station settings, shared by register_station and update_station
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StationParams {
+    pub connector_types: Vec<ConnectorType>,
+    pub charger_power_kw: u16,
+    pub pricing_per_kwh: u64,              // in lamports
+    pub energy_tolerance_pct: u8,          // allowed excess over rated power, for meter/clock skew
+    pub max_session_duration_secs: u32,    // sessions older than this can be expired by anyone
+    pub idle_timeout_secs: u32,            // sessions without metered energy for this long can be expired
+    pub idle_fee_per_minute_lamports: u64, // charged per minute a session stays paused past the grace period
+    pub idle_grace_period_secs: u32,       // paused time allowed before idle fees start
+    pub supports_v2g: bool,                // bidirectional charger, allows discharge sessions
+    pub grid_region: u16,                  // keys the carbon intensity used for avoided emissions
+    pub latitude: i32,                     // Stored as (lat * 1_000_000) for precision
+    pub longitude: i32,                    // Stored as (lng * 1_000_000) for precision
+}
+
+impl StationParams {
+    fn validate(&self) -> Result<()> {
+        require!(
+            !self.connector_types.is_empty() && self.connector_types.len() <= MAX_CONNECTOR_TYPES,
+            ErrorCode::InvalidConnectorTypes
+        );
+        require!(self.charger_power_kw > 0, ErrorCode::InvalidChargerPower);
+        require!(
+            self.energy_tolerance_pct <= MAX_ENERGY_TOLERANCE_PCT,
+            ErrorCode::InvalidEnergyTolerance
+        );
+        require!(
+            self.max_session_duration_secs > 0 && self.idle_timeout_secs > 0,
+            ErrorCode::InvalidSessionTimeout
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum SessionMode {
+    Charge,    // grid to vehicle
+    Discharge, // vehicle to grid (V2G)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ConnectorType {
+    Type1,
+    Type2,
+    Ccs1,
+    Ccs2,
+    Chademo,
+    Nacs,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MeterRegistration {
+    pub meter: Pubkey,
+    #[max_len(20)]
+    pub charger_code: String,
+    pub is_active: bool,
+    pub registered_at: i64,
+    pub bump: u8,
+    pub operator: Pubkey, // the station at charger_code must be registered by this operator
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct VoucherRedemption {
+    pub voucher: Pubkey,
+    pub user: Pubkey,
+    pub points_amount: u64,
+    pub redeemed_at: i64,
+    pub bump: u8,
+}
+
+/// Compact per-user log of closed sessions; oldest entries are overwritten once full
+#[account]
+#[derive(InitSpace)]
+pub struct SessionHistory {
+    pub user: Pubkey,
+    pub total_records: u64, // sessions archived over the account's lifetime
+    pub next_index: u8,     // ring buffer slot the next record is written to
+    pub records: [SessionRecord; SESSION_HISTORY_LEN],
+    pub bump: u8,
+}
+
+impl SessionHistory {
+    fn push(&mut self, record: SessionRecord) {
+        self.records[self.next_index as usize] = record;
+        self.next_index = ((self.next_index as usize + 1) % SESSION_HISTORY_LEN) as u8;
+        self.total_records = self.total_records.saturating_add(1);
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct SessionRecord {
+    pub charger_code_hash: [u8; 32],
+    pub energy_wh: u64,
+    pub points: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub points_per_kwh: u64,
+    pub min_deposit_lamports: u64,
+    pub max_deposit_lamports: u64,
+    pub max_session_duration_secs: u32,
+    pub is_paused: bool,
+    pub bump: u8,
+    // Fields added since the config was created. New fields go at the end so the config can be
+    // grown with upgrade_protocol_config
+    pub export_points_per_kwh: u64,
+    pub ice_baseline_g_co2_per_kwh: u32,
+    pub low_carbon_threshold_g_co2_per_kwh: u32,
+    pub low_carbon_points_per_kwh: u64,
+    pub carbon_oracle: Pubkey, // may publish carbon intensities; default pubkey when unset
+    #[max_len(4)]
+    pub loyalty_tiers: Vec<LoyaltyTier>,
+    #[max_len(4)]
+    pub streak_milestones: Vec<StreakMilestone>,
+    pub grace_day_price_points: u64, // 0 when grace days are not for sale
+    pub referral_terms: ReferralTerms,
+    pub point_lifetime_secs: u32,
+    pub daily_transfer_limit_points: u64,
+    pub current_season: u32,         // 0 until the first season is started
+    pub season_duration_secs: u32,
+    #[max_len(10)]
+    pub season_prizes: Vec<u64>,     // prize points by leaderboard rank
+    pub min_session_duration_secs: u32,
+}
+
+impl ProtocolConfig {
+    pub fn apply(&mut self, params: ProtocolParams) {
+        self.points_per_kwh = params.points_per_kwh;
+        self.export_points_per_kwh = params.export_points_per_kwh;
+        self.min_deposit_lamports = params.min_deposit_lamports;
+        self.max_deposit_lamports = params.max_deposit_lamports;
+        self.max_session_duration_secs = params.max_session_duration_secs;
+        self.min_session_duration_secs = params.min_session_duration_secs;
+        self.ice_baseline_g_co2_per_kwh = params.ice_baseline_g_co2_per_kwh;
+        self.low_carbon_threshold_g_co2_per_kwh = params.low_carbon_threshold_g_co2_per_kwh;
+        self.low_carbon_points_per_kwh = params.low_carbon_points_per_kwh;
+        self.point_lifetime_secs = params.point_lifetime_secs;
+        self.daily_transfer_limit_points = params.daily_transfer_limit_points;
+    }
+
+    /// Highest tier level whose thresholds the driver meets, 0 if none
+    pub fn loyalty_tier_for(&self, user_account: &UserAccount) -> u8 {
+        self.loyalty_tiers
+            .iter()
+            .take_while(|tier| {
+                user_account.total_energy_kwh >= tier.min_energy_kwh
+                    && user_account.total_sessions >= tier.min_sessions
+            })
+            .count() as u8
+    }
+
+    /// Benefits of a tier level; the base level, or a level whose tier was removed, gets none
+    pub fn loyalty_benefits(&self, loyalty_tier: u8) -> LoyaltyBenefits {
+        let tier = (loyalty_tier as usize)
+            .checked_sub(1)
+            .and_then(|index| self.loyalty_tiers.get(index));
+
+        LoyaltyBenefits {
+            loyalty_tier,
+            points_multiplier_bps: tier.map_or(MULTIPLIER_BPS_BASE, |tier| tier.points_multiplier_bps),
+            marketplace_discount_bps: tier.map_or(0, |tier| tier.marketplace_discount_bps),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct LoyaltyTier {
+    pub min_energy_kwh: u64,           // lifetime energy required
+    pub min_sessions: u64,             // lifetime sessions required
+    pub points_multiplier_bps: u16,    // applied to session points, 10_000 = 1x
+    pub marketplace_discount_bps: u16, // off marketplace prices, 10_000 = 100%
+}
+
+impl LoyaltyTier {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            (MULTIPLIER_BPS_BASE..=MAX_MULTIPLIER_BPS).contains(&self.points_multiplier_bps),
+            ErrorCode::InvalidMultiplier
+        );
+        require!(
+            self.marketplace_discount_bps <= MAX_LOYALTY_DISCOUNT_BPS,
+            ErrorCode::InvalidLoyaltyTier
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct StreakMilestone {
+    pub days: u16, // streak length that pays the bonus
+    pub bonus_points: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ReferralTerms {
+    pub reward_pct: u8,              // share of the referee's session points paid to the referrer
+    pub max_sessions: u16,           // rewards stop after this many referee sessions...
+    pub max_energy_kwh: u64,         // ...or once the referee has charged this much, whichever is first
+    pub max_points_per_referee: u64, // lifetime cap on rewards earned through one referee
+}
+
+/// A driver's loyalty tier and what it is worth, returned by get_loyalty_benefits
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoyaltyBenefits {
+    pub loyalty_tier: u8,
+    pub points_multiplier_bps: u16,
+    pub marketplace_discount_bps: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProtocolParams {
+    pub points_per_kwh: u64,
+    pub export_points_per_kwh: u64,     // reward rate for vehicle-to-grid exports
+    pub min_deposit_lamports: u64,      // smallest deposit accepted for a prepaid session
+    pub max_deposit_lamports: u64,      // largest deposit accepted for a prepaid session
+    pub max_session_duration_secs: u32, // caps every station's max session duration
+    pub min_session_duration_secs: u32, // drivers cannot end a session sooner, 0 for no minimum
+    pub ice_baseline_g_co2_per_kwh: u32,         // emissions of an ICE car covering the distance of 1 kWh
+    pub low_carbon_threshold_g_co2_per_kwh: u32, // grid intensity at or below which the bonus applies
+    pub low_carbon_points_per_kwh: u64,          // bonus rate for low-carbon charging, 0 to disable
+    pub point_lifetime_secs: u32,                // points expire this long after they are credited, 0 for never
+    pub daily_transfer_limit_points: u64,        // most points a driver can send per UTC day, 0 disables transfers
+}
+
+impl ProtocolParams {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.min_deposit_lamports <= self.max_deposit_lamports,
+            ErrorCode::InvalidProtocolConfig
+        );
+        require!(
+            self.max_session_duration_secs > 0
+                && self.min_session_duration_secs < self.max_session_duration_secs,
+            ErrorCode::InvalidProtocolConfig
+        );
+        require!(
+            self.ice_baseline_g_co2_per_kwh <= MAX_CARBON_INTENSITY_G_PER_KWH
+                && self.low_carbon_threshold_g_co2_per_kwh <= MAX_CARBON_INTENSITY_G_PER_KWH,
+            ErrorCode::InvalidProtocolConfig
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MultiplierSchedule {
+    #[max_len(8)]
+    pub windows: Vec<TouWindow>,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl MultiplierSchedule {
+    /// Highest multiplier among the windows covering `now`, or 1x if none do
+    pub fn multiplier_at(&self, now: i64) -> u16 {
+        let weekday = (now.div_euclid(86_400) + 4).rem_euclid(7) as u8; // 1970-01-01 was a Thursday
+        let hour = (now.rem_euclid(86_400) / 3600) as u8;
+
+        self.windows
+            .iter()
+            .filter(|window| window.covers(weekday, hour))
+            .map(|window| window.multiplier_bps)
+            .fold(MULTIPLIER_BPS_BASE, u16::max)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct TouWindow {
+    pub weekday_mask: u8,    // bit 0 = Sunday ... bit 6 = Saturday (UTC)
+    pub start_hour: u8,      // inclusive, UTC
+    pub end_hour: u8,        // exclusive, UTC; a window with end_hour <= start_hour wraps past midnight
+    pub multiplier_bps: u16, // 10_000 = 1x
+}
+
+impl TouWindow {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.weekday_mask != 0 && self.weekday_mask < 1 << 7,
+            ErrorCode::InvalidTouWindow
+        );
+        require!(self.start_hour < 24 && self.end_hour < 24, ErrorCode::InvalidTouWindow);
+        require!(
+            (MULTIPLIER_BPS_BASE..=MAX_MULTIPLIER_BPS).contains(&self.multiplier_bps),
+            ErrorCode::InvalidMultiplier
+        );
+        Ok(())
+    }
+
+    /// A window that wraps past midnight belongs to the weekday it starts on, so its
+    /// post-midnight hours are checked against the previous day's bit
+    pub fn covers(&self, weekday: u8, hour: u8) -> bool {
+        let start_day = if self.start_hour < self.end_hour {
+            if hour < self.start_hour || hour >= self.end_hour {
+                return false;
+            }
+            weekday
+        } else if hour >= self.start_hour {
+            weekday
+        } else if hour < self.end_hour {
+            (weekday + 6) % 7
+        } else {
+            return false;
+        };
+        self.weekday_mask & (1 << start_day) != 0
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct GridOperator {
+    pub authority: Pubkey,
+    pub registered_at: i64,
+    pub bump: u8,
+    pub grid_region: u16, // region the operator publishes demand-response events for
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct DemandResponseEvent {
+    pub grid_operator: Pubkey,
+    pub event_id: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub multiplier_bps: u16, // 10_000 = 1x
+    pub bump: u8,
+    pub grid_region: u16,    // sessions at stations in this region apply the event
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CarbonIntensity {
+    pub region: u16,
+    pub g_co2_per_kwh: u32,
+    pub updated_by: Pubkey, // admin or carbon oracle
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl DemandResponseEvent {
+    /// The event multiplier while it is running, 1x otherwise
+    pub fn multiplier_at(&self, now: i64) -> u16 {
+        if now >= self.start_time && now < self.end_time {
+            self.multiplier_bps
+        } else {
+            MULTIPLIER_BPS_BASE
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct AuthorizedCaller {
+    pub program_id: Pubkey,
+    pub authority: Pubkey, // PDA of program_id derived from authority_seed
+    #[max_len(32)]
+    pub authority_seed: Vec<u8>,
+    pub authority_bump: u8,
+    pub daily_mint_cap: u64,
+    pub minted_today: u64,
+    pub current_day: i64, // days since the unix epoch that minted_today refers to
+    pub bump: u8,
+}
+
+impl AuthorizedCaller {
+    /// Count a mint against the daily cap, resetting the counter on a new UTC day
+    pub fn record_mint(&mut self, amount: u64, now: i64) -> Result<()> {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        if today != self.current_day {
+            self.current_day = today;
+            self.minted_today = 0;
+        }
+
+        let minted = self.minted_today
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(minted <= self.daily_mint_cap, ErrorCode::DailyMintCapExceeded);
+
+        self.minted_today = minted;
+        Ok(())
+    }
+}
+
+#[event]
+pub struct SessionStarted {
+    pub session: Pubkey,
+    pub user: Pubkey,
+    pub station: Pubkey,
+    pub charger_code: String,
+    pub mode: SessionMode,
+    pub meter: Pubkey,
+    pub deposit_lamports: u64,
+    pub points_per_kwh: u64,
+    pub start_time: i64,
+}
+
+#[event]
+pub struct SessionUpdated {
+    pub session: Pubkey,
+    pub reading_sequence: u64,
+    pub energy_consumed_wh: u64,
+    pub energy_exported_wh: u64,
+    pub points_earned: u64,
+    pub tou_multiplier_bps: u16,
+    pub dr_multiplier_bps: u16,
+    pub deposit_exhausted: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MeterAnomalyRecorded {
+    pub session: Pubkey,
+    pub reading_sequence: u64,
+    pub reported_wh: u64,
+    pub max_plausible_wh: u64,
+    pub anomaly_count: u32,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SessionEnded {
+    pub session: Pubkey,
+    pub user: Pubkey,
+    pub energy_consumed_wh: u64,
+    pub energy_exported_wh: u64,
+    pub points_earned: u64,
+    pub amount_paid_lamports: u64,
+    pub idle_fee_lamports: u64,
+    pub co2_avoided_g: u64,
+    pub loyalty_tier: u8, // driver's tier after this session
+    pub achievement_bonus_points: u64, // minted on top of points_earned
+    pub streak_bonus_points: u64,      // minted on top of points_earned
+    pub end_time: i64,
+    pub expired_by: Option<Pubkey>, // cranker, when ended by expire_session
+}
+
+#[event]
+pub struct SessionDelegateGranted {
+    pub session: Pubkey,
+    pub delegate: Pubkey,
+    pub scope: u8,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct SessionDelegateRevoked {
+    pub session: Pubkey,
+    pub delegate: Pubkey,
+}
+
+#[event]
+pub struct SessionPaused {
+    pub session: Pubkey,
+    pub paused_at: i64,
+}
+
+#[event]
+pub struct SessionResumed {
+    pub session: Pubkey,
+    pub resumed_at: i64,
+    pub total_paused_secs: u64,
+}
+
+#[event]
+pub struct SessionClosed {
+    pub session: Pubkey,
+    pub user: Pubkey,
+    pub history_records: u64,
+}
+
+#[event]
+pub struct UserInitialized {
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub points_account: Pubkey,
+}
+
+#[event]
+pub struct PointsCredited {
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub caller: Pubkey,
+}
+
+#[event]
+pub struct PointsDebited {
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub caller: Pubkey,
+}
+
+#[event]
+pub struct PointsMigrated {
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct VoucherRedeemed {
+    pub voucher: Pubkey,
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub points_amount: u64,
+    pub redeemed_at: i64,
+}
+
+#[event]
+pub struct CarbonIntensityUpdated {
+    pub region: u16,
+    pub g_co2_per_kwh: u32,
+    pub updated_by: Pubkey,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct AchievementUnlocked {
+    pub user: Pubkey,
+    pub achievement: u32, // ACHIEVEMENT_* flag
+    pub bonus_points: u64,
+    pub unlocked_at: i64,
+}
+
+#[event]
+pub struct StreakMilestoneReached {
+    pub user: Pubkey,
+    pub streak_days: u16,
+    pub bonus_points: u64,
+}
+
+#[event]
+pub struct GraceDayPurchased {
+    pub user: Pubkey,
+    pub price_points: u64,
+    pub grace_days: u8, // banked after the purchase
+}
+
+#[event]
+pub struct ReferralRewarded {
+    pub referrer: Pubkey,
+    pub referee: Pubkey,
+    pub session: Pubkey,
+    pub points: u64,
+}
+
+#[event]
+pub struct PointsExpired {
+    pub user_account: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PointsTransferred {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+    pub memo: Option<String>,
+}
+
+#[event]
+pub struct PointsMintInitialized {
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct StationRegistered {
+    pub station: Pubkey,
+    pub operator: Pubkey,
+    pub code: String,
+    pub charger_power_kw: u16,
+    pub pricing_per_kwh: u64,
+    pub grid_region: u16,
+}
+
+#[event]
+pub struct StationUpdated {
+    pub station: Pubkey,
+    pub charger_power_kw: u16,
+    pub pricing_per_kwh: u64,
+    pub grid_region: u16,
+    pub supports_v2g: bool,
+}
+
+#[event]
+pub struct StationRetired {
+    pub station: Pubkey,
+    pub code: String,
+}
+
+#[event]
+pub struct MeterRegistered {
+    pub meter: Pubkey,
+    pub charger_code: String,
+    pub operator: Pubkey,
+}
+
+#[event]
+pub struct MeterStatusChanged {
+    pub meter: Pubkey,
+    pub is_active: bool,
+}
+
+#[event]
+pub struct ProtocolConfigUpdated {
+    pub admin: Pubkey,
+    pub params: ProtocolParams, // as applied, also emitted by initialize_protocol_config
+}
+
+#[event]
+pub struct ProtocolPauseChanged {
+    pub is_paused: bool,
+}
+
+#[event]
+pub struct ProtocolAdminChanged {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct LoyaltyTiersUpdated {
+    pub tiers: Vec<LoyaltyTier>,
+}
+
+#[event]
+pub struct StreakRewardsUpdated {
+    pub milestones: Vec<StreakMilestone>,
+    pub grace_day_price_points: u64,
+}
+
+#[event]
+pub struct ReferralTermsUpdated {
+    pub terms: ReferralTerms,
+}
+
+#[event]
+pub struct TouScheduleUpdated {
+    pub windows: Vec<TouWindow>,
+    pub updated_at: i64,
+}
+
+#[event]
+pub struct GridOperatorRegistered {
+    pub authority: Pubkey,
+    pub grid_region: u16,
+}
+
+#[event]
+pub struct GridOperatorRemoved {
+    pub authority: Pubkey,
+    pub grid_region: u16,
+}
+
+#[event]
+pub struct DemandResponsePublished {
+    pub grid_operator: Pubkey,
+    pub grid_region: u16,
+    pub event_id: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub multiplier_bps: u16,
+}
+
+#[event]
+pub struct DemandResponseCancelled {
+    pub grid_operator: Pubkey,
+    pub grid_region: u16,
+    pub event_id: u64,
+}
+
+#[event]
+pub struct CarbonOracleUpdated {
+    pub oracle: Pubkey,
+}
+
+#[event]
+pub struct AuthorizedCallerRegistered {
+    pub program_id: Pubkey,
+    pub authority: Pubkey, // PDA of program_id that signs its CPIs
+    pub daily_mint_cap: u64,
+}
+
+#[event]
+pub struct CallerMintCapUpdated {
+    pub program_id: Pubkey,
+    pub daily_mint_cap: u64,
+}
+
+#[event]
+pub struct AuthorizedCallerRemoved {
+    pub program_id: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Fleet {
+    pub owner: Pubkey,
+    pub fleet_id: u64, // chosen by the owner, distinguishes their fleets
+    #[max_len(32)]
+    pub name: String,
+    #[max_len(5)]
+    pub admins: Vec<Pubkey>, // may manage members alongside the owner
+    pub member_count: u32,
+    pub total_energy_wh: u64,     // members' sessions while in the fleet
+    pub total_sessions: u64,
+    pub total_points_pooled: u64, // paid into the fleet pool by members' sessions
+    #[max_len(12)]
+    pub point_lots: Vec<PointLot>, // pool points, oldest first; withdrawals keep their expiry
+    pub expired_points: u64,       // pool points burned by expire_fleet_points
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Fleet {
+    pub fn is_admin(&self, key: &Pubkey) -> bool {
+        self.owner == *key || self.admins.contains(key)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FleetMember {
+    pub fleet: Pubkey,
+    pub member: Pubkey,             // driver authority
+    pub spending_limit_points: u64, // most pool points the member may withdraw
+    pub points_spent: u64,          // pool points withdrawn so far
+    pub points_pooled: u64,         // paid into the pool by the member's sessions
+    pub energy_wh: u64,
+    pub sessions: u64,
+    pub joined_at: i64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct FleetCreated {
+    pub fleet: Pubkey,
+    pub owner: Pubkey,
+    pub name: String,
+}
+
+#[event]
+pub struct FleetAdminAdded {
+    pub fleet: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct FleetAdminRemoved {
+    pub fleet: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct FleetMemberAdded {
+    pub fleet: Pubkey,
+    pub member: Pubkey,
+    pub spending_limit_points: u64,
+}
+
+#[event]
+pub struct FleetMemberLimitUpdated {
+    pub fleet: Pubkey,
+    pub member: Pubkey,
+    pub spending_limit_points: u64,
+}
+
+#[event]
+pub struct FleetMemberRemoved {
+    pub fleet: Pubkey,
+    pub member: Pubkey,
+    pub removed_by: Pubkey,
+}
+
+#[event]
+pub struct FleetPointsPooled {
+    pub fleet: Pubkey,
+    pub member: Pubkey,
+    pub session: Pubkey,
+    pub points: u64,
+}
+
+#[event]
+pub struct FleetPointsWithdrawn {
+    pub fleet: Pubkey,
+    pub member: Pubkey,
+    pub amount: u64,
+    pub points_spent: u64, // member total after the withdrawal
+}
+
+#[event]
+pub struct FleetPointsExpired {
+    pub fleet: Pubkey,
+    pub amount: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Season {
+    pub season_id: u32, // numbered from 1
+    pub start_time: i64,
+    pub end_time: i64,  // sessions ending at or after this close the season and count towards the next
+    #[max_len(10)]
+    pub leaderboard: Vec<SeasonEntry>, // highest points first
+    pub is_closed: bool,
+    #[max_len(10)]
+    pub prizes: Vec<u64>, // prize points by rank, fixed by close_season
+    pub prizes_paid: u16, // bit n set once the prize for rank n + 1 is paid
+    pub bump: u8,
+}
+
+impl Season {
+    /// Insert or update a driver's season totals, keeping the top MAX_SEASON_ENTRIES by points then energy
+    pub fn record(&mut self, entry: SeasonEntry) {
+        let rank_key = |entry: &SeasonEntry| (entry.points, entry.energy_wh);
+
+        if let Some(existing) = self.leaderboard.iter_mut().find(|existing| existing.user == entry.user) {
+            *existing = entry;
+        } else if self.leaderboard.len() < MAX_SEASON_ENTRIES {
+            self.leaderboard.push(entry);
+        } else if let Some(last) = self.leaderboard.last_mut() {
+            if rank_key(&entry) <= rank_key(last) {
+                return;
+            }
+            *last = entry;
+        }
+
+        // Stable, so earlier entries keep their place on ties
+        self.leaderboard.sort_by_key(|entry| std::cmp::Reverse(rank_key(entry)));
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct SeasonEntry {
+    pub user: Pubkey, // driver authority
+    pub energy_wh: u64,
+    pub points: u64,
+}
+
+#[event]
+pub struct SeasonRewardsUpdated {
+    pub duration_secs: u32, // applies to seasons started afterwards
+    pub prizes: Vec<u64>,
+}
+
+#[event]
+pub struct SeasonStarted {
+    pub season_id: u32,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+#[event]
+pub struct SeasonClosed {
+    pub season_id: u32,
+    pub winners: Vec<SeasonEntry>,
+    pub prizes: Vec<u64>,
+}
+
+#[event]
+pub struct SeasonPrizePaid {
+    pub season_id: u32,
+    pub user: Pubkey,
+    pub rank: u8,
+    pub points: u64,
 }
 
 #[error_code]
@@ -385,4 +5499,156 @@ pub enum ErrorCode {
     VoucherAlreadyRedeemed,
     #[msg("Unauthorized caller - only whitelisted programs can modify points")]
     UnauthorizedCaller,
+    #[msg("Meter reading is missing its Ed25519 signature instruction")]
+    MissingMeterSignature,
+    #[msg("Meter reading signature does not match the session meter or reading")]
+    InvalidMeterSignature,
+    #[msg("Meter reading sequence must increase - stale or replayed reading")]
+    StaleMeterReading,
+    #[msg("Cumulative energy reading is lower than the last attested reading")]
+    EnergyReadingRegressed,
+    #[msg("Meter is not active")]
+    MeterInactive,
+    #[msg("Meter is not registered for this charger")]
+    MeterChargerMismatch,
+    #[msg("Unauthorized admin - only the program upgrade authority can do this")]
+    UnauthorizedAdmin,
+    #[msg("Charger station is not active")]
+    StationInactive,
+    #[msg("Invalid charger code - must be 1 to 20 characters")]
+    InvalidChargerCode,
+    #[msg("Invalid connector types - must list 1 to 6 connectors")]
+    InvalidConnectorTypes,
+    #[msg("Invalid charger power - must be greater than zero")]
+    InvalidChargerPower,
+    #[msg("Invalid energy tolerance - must be at most 50 percent")]
+    InvalidEnergyTolerance,
+    #[msg("Escrow account must be provided exactly when the session has a deposit")]
+    EscrowMismatch,
+    #[msg("Operator does not match the session escrow")]
+    InvalidOperator,
+    #[msg("No legacy points to migrate")]
+    NoPointsToMigrate,
+    #[msg("Session is still active - end it before closing")]
+    SessionStillActive,
+    #[msg("Session timeouts must be greater than zero")]
+    InvalidSessionTimeout,
+    #[msg("Session has not reached its max duration or idle timeout")]
+    SessionNotExpired,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("Invalid protocol config: deposit bounds or session duration")]
+    InvalidProtocolConfig,
+    #[msg("Deposit is outside the protocol's allowed range")]
+    DepositOutOfRange,
+    #[msg("Invalid time-of-use window")]
+    InvalidTouWindow,
+    #[msg("Multiplier must be between 1x and 5x")]
+    InvalidMultiplier,
+    #[msg("Demand response events must end after they start and last at most 24 hours")]
+    InvalidDemandResponseWindow,
+    #[msg("Caller authority seed must be 1-32 bytes")]
+    InvalidCallerSeed,
+    #[msg("Authorized caller has reached its daily mint cap")]
+    DailyMintCapExceeded,
+    #[msg("Voucher has expired")]
+    VoucherExpired,
+    #[msg("Signer is neither the session driver nor a delegate with this permission")]
+    UnauthorizedDelegate,
+    #[msg("Delegate scope must be a non-empty combination of update and end permissions")]
+    InvalidDelegateScope,
+    #[msg("Delegate expiry must be in the future")]
+    InvalidDelegateExpiry,
+    #[msg("Session has no delegate")]
+    NoSessionDelegate,
+    #[msg("Session is paused")]
+    SessionPaused,
+    #[msg("Session is not paused")]
+    SessionNotPaused,
+    #[msg("Station does not support vehicle-to-grid discharge")]
+    StationNotV2g,
+    #[msg("Only discharge sessions can report exported energy")]
+    ExportNotAllowed,
+    #[msg("Only the protocol admin or carbon oracle can publish carbon intensities")]
+    UnauthorizedCarbonOracle,
+    #[msg("Carbon intensity is out of range")]
+    InvalidCarbonIntensity,
+    #[msg("Loyalty tiers must be ordered by threshold with a discount of at most 50%")]
+    InvalidLoyaltyTier,
+    #[msg("Streak milestones must be ordered by strictly increasing, non-zero days")]
+    InvalidStreakMilestone,
+    #[msg("Grace days are not for sale")]
+    GraceDaysUnavailable,
+    #[msg("Maximum number of grace days already banked")]
+    TooManyGraceDays,
+    #[msg("Drivers cannot refer themselves")]
+    SelfReferral,
+    #[msg("Referral reward share is too high")]
+    InvalidReferralTerms,
+    #[msg("The driver's referrer accounts must be provided")]
+    ReferrerAccountMissing,
+    #[msg("Referrer does not match the driver's referral")]
+    InvalidReferrer,
+    #[msg("No point lots have expired")]
+    NoExpiredPoints,
+    #[msg("Transfer amount must be greater than zero")]
+    InvalidTransferAmount,
+    #[msg("Transfer memo is too long")]
+    TransferMemoTooLong,
+    #[msg("Points cannot be transferred to yourself")]
+    InvalidTransferRecipient,
+    #[msg("Daily point transfer limit exceeded")]
+    DailyTransferLimitExceeded,
+    #[msg("Fleet name must be 1-32 characters")]
+    InvalidFleetName,
+    #[msg("Only a fleet admin can do this")]
+    UnauthorizedFleetAdmin,
+    #[msg("Key is already a fleet admin")]
+    FleetAdminExists,
+    #[msg("Key is not a fleet admin")]
+    FleetAdminNotFound,
+    #[msg("Fleet has the maximum number of admins")]
+    TooManyFleetAdmins,
+    #[msg("Driver already belongs to a fleet")]
+    AlreadyInFleet,
+    #[msg("The driver's fleet accounts must be provided")]
+    FleetAccountMissing,
+    #[msg("Fleet accounts do not match the driver's fleet")]
+    InvalidFleetAccount,
+    #[msg("Fleet member spending limit exceeded")]
+    FleetSpendingLimitExceeded,
+    #[msg("Season length must be positive with at most 10 prizes")]
+    InvalidSeasonTerms,
+    #[msg("A season is already running")]
+    SeasonAlreadyRunning,
+    #[msg("Season is not the current season")]
+    SeasonNotCurrent,
+    #[msg("Season has not ended yet")]
+    SeasonNotOver,
+    #[msg("Season has not been closed yet")]
+    SeasonNotClosed,
+    #[msg("Driver did not win a prize this season")]
+    NotASeasonWinner,
+    #[msg("Season prize already paid")]
+    SeasonPrizeAlreadyPaid,
+    #[msg("Account is not an older version of the expected account")]
+    InvalidAccountUpgrade,
+    #[msg("Account already has the current layout")]
+    AccountUpToDate,
+    #[msg("Station is not registered by the operator of its meter")]
+    MeterOperatorMismatch,
+    #[msg("Another grid operator's demand-response event is still running in this region")]
+    DemandResponseSlotInUse,
+    #[msg("Only the grid operator that published the event can cancel it")]
+    UnauthorizedGridOperator,
+    #[msg("Account is not owned by this program")]
+    InvalidAccountOwner,
+    #[msg("Point transfers are disabled")]
+    TransfersDisabled,
+    #[msg("The current season must be provided")]
+    SeasonAccountMissing,
+    #[msg("The season is over; the next season account must be provided")]
+    NextSeasonMissing,
+    #[msg("Session has not reached the protocol's minimum duration")]
+    SessionTooShort,
 }
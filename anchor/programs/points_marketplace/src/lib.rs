@@ -6,7 +6,17 @@ declare_id!("9PQHr2B1MoxNwyjwdvxZcc7VifqKsetsjvikGwxu2Eko");
 // Charging session program ID (for CPI authorization)
 pub const CHARGING_SESSION_PROGRAM_ID: Pubkey = pubkey!("5emVuARWebNveyqe9ivrM24yhBMdLWJvq3qzYTDDd66u");
 
+// Seed of the charging_session PDA that signs mark_voucher_redeemed
+pub const VOUCHER_REDEEMER_SEED: &[u8] = b"voucher_redeemer";
+
+// charging_session accounts and instruction read for a buyer's loyalty discount
+const PROTOCOL_CONFIG_SEED: &[u8] = b"protocol_config";
+const USER_ACCOUNT_SEED: &[u8] = b"user";
+const GET_LOYALTY_BENEFITS_DISCRIMINATOR: [u8; 8] = [60, 198, 144, 56, 196, 210, 105, 153];
+const BPS_BASE: u64 = 10_000;
+
 const DISCOUNT_PERCENTAGE: u64 = 50; // Web3 users get 50% discount
+const VOUCHER_VALIDITY_SECS: i64 = 30 * 24 * 3600; // default: vouchers must be redeemed within 30 days
 
 #[program]
 pub mod points_marketplace {
@@ -21,12 +31,60 @@ pub mod points_marketplace {
         marketplace.total_revenue_lamports = 0;
         marketplace.price_per_point_lamports = 1_000_000; // 0.001 SOL per point
         marketplace.bump = ctx.bumps.marketplace;
+        marketplace.voucher_validity_secs = VOUCHER_VALIDITY_SECS;
+
+        emit!(MarketplaceInitialized {
+            marketplace: marketplace.key(),
+            authority: marketplace.authority,
+            price_per_point_lamports: marketplace.price_per_point_lamports,
+        });
 
         msg!("Marketplace initialized with price: {} lamports per point",
              marketplace.price_per_point_lamports);
         Ok(())
     }
 
+    /// Set how long newly issued vouchers stay redeemable (authority only)
+    /// Vouchers already issued keep the expiry they were issued with
+    pub fn set_voucher_validity(ctx: Context<UpdateMarketplace>, validity_secs: i64) -> Result<()> {
+        require!(validity_secs > 0, ErrorCode::InvalidVoucherValidity);
+
+        let marketplace = &mut ctx.accounts.marketplace;
+        marketplace.voucher_validity_secs = validity_secs;
+
+        emit!(VoucherValidityUpdated {
+            marketplace: marketplace.key(),
+            validity_secs,
+        });
+
+        msg!("Vouchers now valid for {} seconds", validity_secs);
+        Ok(())
+    }
+
+    /// Grow a marketplace created before the voucher validity was configurable
+    /// The appended validity starts at the default; anyone may pay the extra rent
+    pub fn upgrade_marketplace(ctx: Context<UpgradeMarketplace>) -> Result<()> {
+        let marketplace_info = &ctx.accounts.marketplace;
+
+        grow_account(
+            marketplace_info,
+            Marketplace::DISCRIMINATOR,
+            8 + Marketplace::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ErrorCode::InvalidMarketplaceUpgrade,
+            ErrorCode::MarketplaceUpToDate,
+        )?;
+
+        let mut data = marketplace_info.try_borrow_mut_data()?;
+        let mut marketplace = Marketplace::try_deserialize(&mut &data[..])?;
+        marketplace.voucher_validity_secs = VOUCHER_VALIDITY_SECS;
+        marketplace.try_serialize(&mut &mut data[..])?;
+
+        msg!("Marketplace upgraded, vouchers valid for {} seconds", marketplace.voucher_validity_secs);
+        Ok(())
+    }
+
     /// Create a sell listing (drivers selling their points)
     /// Note: Seller must have points in their charging_session account
     /// Listing is a commitment - points stay in seller's account until purchase
@@ -45,12 +103,21 @@ pub mod points_marketplace {
         listing.created_at = timestamp;
         listing.bump = ctx.bumps.listing;
 
+        emit!(ListingCreated {
+            listing: listing.key(),
+            seller: listing.seller,
+            points_amount,
+            price_per_point,
+            created_at: timestamp,
+        });
+
         msg!("Listing created: {} points at {} lamports each",
              points_amount, price_per_point);
         Ok(())
     }
 
     /// Buy points from marketplace at 50% discount (Web3 users)
+    /// The buyer's charging_session loyalty tier takes its marketplace discount off the discounted price
     /// Issues a voucher that can be redeemed in charging_session program
     pub fn buy_from_marketplace(
         ctx: Context<BuyFromMarketplace>,
@@ -71,6 +138,16 @@ pub mod points_marketplace {
             .checked_div(100)
             .ok_or(ErrorCode::DivisionByZero)?;
 
+        let loyalty_discount_bps = loyalty_discount_bps(
+            &ctx.accounts.charging_program,
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.user_account,
+        )?;
+        let discounted_price = discounted_price
+            .checked_mul(BPS_BASE - loyalty_discount_bps)
+            .ok_or(ErrorCode::Overflow)?
+            / BPS_BASE;
+
         // Transfer SOL from buyer to marketplace
         let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
             &ctx.accounts.buyer.key(),
@@ -92,10 +169,20 @@ pub mod points_marketplace {
         voucher.points_amount = points_amount;
         voucher.is_redeemed = false;
         voucher.created_at = timestamp;
+        voucher.expires_at = Clock::get()?.unix_timestamp + marketplace.voucher_validity_secs;
         voucher.bump = ctx.bumps.voucher;
 
-        msg!("Purchased {} points for {} lamports (50% discount) - voucher created",
-             points_amount, discounted_price);
+        emit!(VoucherIssued {
+            voucher: voucher.key(),
+            buyer: voucher.buyer,
+            points_amount,
+            price_lamports: discounted_price,
+            listing: None,
+            created_at: timestamp,
+        });
+
+        msg!("Purchased {} points for {} lamports (50% discount, {} bps loyalty discount) - voucher created",
+             points_amount, discounted_price, loyalty_discount_bps);
         Ok(())
     }
 
@@ -135,11 +222,30 @@ pub mod points_marketplace {
         voucher.points_amount = listing.points_amount;
         voucher.is_redeemed = false;
         voucher.created_at = timestamp;
+        voucher.expires_at = Clock::get()?.unix_timestamp + ctx.accounts.marketplace.voucher_validity_secs;
         voucher.bump = ctx.bumps.voucher;
 
         // Deactivate listing
         listing.is_active = false;
 
+        emit!(VoucherIssued {
+            voucher: voucher.key(),
+            buyer: voucher.buyer,
+            points_amount: listing.points_amount,
+            price_lamports: total_price,
+            listing: Some(listing.key()),
+            created_at: timestamp,
+        });
+
+        emit!(ListingPurchased {
+            listing: listing.key(),
+            seller: listing.seller,
+            buyer: voucher.buyer,
+            voucher: voucher.key(),
+            points_amount: listing.points_amount,
+            total_price_lamports: total_price,
+        });
+
         msg!("Bought {} points for {} lamports from listing - voucher created",
              listing.points_amount, total_price);
         Ok(())
@@ -154,31 +260,153 @@ pub mod points_marketplace {
 
         listing.is_active = false;
 
+        emit!(ListingCancelled {
+            listing: listing.key(),
+            seller: listing.seller,
+        });
+
         msg!("Listing cancelled");
         Ok(())
     }
 
     /// Mark a voucher as redeemed (CPI from charging_session program)
-    /// SECURITY: Only the charging_session program can call this
+    /// SECURITY: Only the charging_session program can call this, by signing with its
+    /// voucher redeemer PDA
     pub fn mark_voucher_redeemed(ctx: Context<MarkVoucherRedeemed>) -> Result<()> {
-        // Verify caller is the charging session program
-        let caller = ctx.accounts.caller_authority.key();
-        require!(
-            caller == CHARGING_SESSION_PROGRAM_ID,
+        // Verify caller is the charging session program's redeemer PDA
+        let (expected_caller, _) = Pubkey::find_program_address(
+            &[VOUCHER_REDEEMER_SEED],
+            &CHARGING_SESSION_PROGRAM_ID,
+        );
+        require_keys_eq!(
+            ctx.accounts.caller_authority.key(),
+            expected_caller,
             ErrorCode::UnauthorizedCaller
         );
 
         let voucher = &mut ctx.accounts.voucher;
 
-        // Check if already redeemed
+        // Check if already redeemed or expired
         require!(!voucher.is_redeemed, ErrorCode::VoucherAlreadyRedeemed);
+        require!(
+            Clock::get()?.unix_timestamp < voucher.expires_at,
+            ErrorCode::VoucherExpired
+        );
 
         // Mark as redeemed
         voucher.is_redeemed = true;
 
+        emit!(VoucherMarkedRedeemed {
+            voucher: voucher.key(),
+            buyer: voucher.buyer,
+            points_amount: voucher.points_amount,
+        });
+
         msg!("Voucher marked as redeemed");
         Ok(())
     }
+
+    /// Grow a voucher issued before vouchers carried an expiry
+    /// The voucher gets the standard validity counted from its issue time; anyone may pay the extra rent
+    pub fn upgrade_voucher(ctx: Context<UpgradeVoucher>) -> Result<()> {
+        let voucher_info = &ctx.accounts.voucher;
+
+        // The appended expires_at starts zeroed
+        grow_account(
+            voucher_info,
+            PointsVoucher::DISCRIMINATOR,
+            8 + PointsVoucher::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            ErrorCode::InvalidVoucherUpgrade,
+            ErrorCode::VoucherUpToDate,
+        )?;
+
+        let mut data = voucher_info.try_borrow_mut_data()?;
+        let mut voucher = PointsVoucher::try_deserialize(&mut &data[..])?;
+        voucher.expires_at = voucher.created_at
+            .checked_add(VOUCHER_VALIDITY_SECS)
+            .ok_or(ErrorCode::Overflow)?;
+        voucher.try_serialize(&mut &mut data[..])?;
+
+        msg!("Voucher {} upgraded, expires at {}", voucher_info.key(), voucher.expires_at);
+        Ok(())
+    }
+}
+
+/// Grow an account of this program written with an older, shorter layout to `new_len`
+/// Appended bytes are zeroed; the payer tops the account up to the rent-exempt minimum for its
+/// new size. Fails with `invalid_upgrade` for any other account and `up_to_date` once grown
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    discriminator: &[u8],
+    new_len: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    invalid_upgrade: ErrorCode,
+    up_to_date: ErrorCode,
+) -> Result<()> {
+    let is_older_version = *account.owner == crate::ID && {
+        let data = account.try_borrow_data()?;
+        data.len() >= discriminator.len() && data[..discriminator.len()] == *discriminator
+    };
+    if !is_older_version {
+        return Err(invalid_upgrade.into());
+    }
+    if account.data_len() >= new_len {
+        return Err(up_to_date.into());
+    }
+
+    let rent_due = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let transfer_instruction = anchor_lang::solana_program::system_instruction::transfer(
+            &payer.key(),
+            &account.key(),
+            rent_due,
+        );
+
+        anchor_lang::solana_program::program::invoke(
+            &transfer_instruction,
+            &[
+                payer.to_account_info(),
+                account.clone(),
+                system_program.to_account_info(),
+            ],
+        )?;
+    }
+
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// Marketplace discount of the buyer's loyalty tier, in basis points
+/// Read from charging_session's get_loyalty_benefits through CPI, so the tier table stays in one place
+fn loyalty_discount_bps<'info>(
+    charging_program: &AccountInfo<'info>,
+    protocol_config: &AccountInfo<'info>,
+    user_account: &AccountInfo<'info>,
+) -> Result<u64> {
+    let instruction = anchor_lang::solana_program::instruction::Instruction {
+        program_id: CHARGING_SESSION_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new_readonly(protocol_config.key(), false),
+            AccountMeta::new_readonly(user_account.key(), false),
+        ],
+        data: GET_LOYALTY_BENEFITS_DISCRIMINATOR.to_vec(),
+    };
+    anchor_lang::solana_program::program::invoke(
+        &instruction,
+        &[protocol_config.clone(), user_account.clone(), charging_program.clone()],
+    )?;
+
+    let (program_id, data) = anchor_lang::solana_program::program::get_return_data()
+        .ok_or(ErrorCode::LoyaltyBenefitsUnavailable)?;
+    require_keys_eq!(program_id, CHARGING_SESSION_PROGRAM_ID, ErrorCode::LoyaltyBenefitsUnavailable);
+    let benefits = LoyaltyBenefits::try_from_slice(&data)
+        .map_err(|_| ErrorCode::LoyaltyBenefitsUnavailable)?;
+
+    // charging_session caps tier discounts well below 100%; clamp anyway so the price cannot underflow
+    Ok((benefits.marketplace_discount_bps as u64).min(BPS_BASE))
 }
 
 #[derive(Accounts)]
@@ -198,6 +426,31 @@ pub struct InitializeMarketplace<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateMarketplace<'info> {
+    #[account(
+        mut,
+        seeds = [b"marketplace"],
+        bump = marketplace.bump,
+        has_one = authority
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpgradeMarketplace<'info> {
+    /// CHECK: May not decode with the current layout yet - owner and discriminator checked in the instruction
+    #[account(mut, seeds = [b"marketplace"], bump)]
+    pub marketplace: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 #[instruction(points_amount: u64, price_per_point: u64, timestamp: i64)]
 pub struct CreateListing<'info> {
@@ -238,12 +491,31 @@ pub struct BuyFromMarketplace<'info> {
     #[account(mut)]
     pub buyer: Signer<'info>,
 
+    /// CHECK: charging_session protocol config - validated by seeds, read by get_loyalty_benefits
+    #[account(seeds = [PROTOCOL_CONFIG_SEED], bump, seeds::program = CHARGING_SESSION_PROGRAM_ID)]
+    pub protocol_config: UncheckedAccount<'info>,
+
+    /// CHECK: The buyer's charging_session user account - validated by seeds, read by get_loyalty_benefits
+    #[account(
+        seeds = [USER_ACCOUNT_SEED, buyer.key().as_ref()],
+        bump,
+        seeds::program = CHARGING_SESSION_PROGRAM_ID
+    )]
+    pub user_account: UncheckedAccount<'info>,
+
+    /// CHECK: charging_session program - validated by address
+    #[account(address = CHARGING_SESSION_PROGRAM_ID)]
+    pub charging_program: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(timestamp: i64)]
 pub struct BuyFromListing<'info> {
+    #[account(seeds = [b"marketplace"], bump = marketplace.bump)]
+    pub marketplace: Account<'info, Marketplace>,
+
     #[account(
         mut,
         seeds = [b"listing", listing.seller.as_ref(), &listing.created_at.to_le_bytes()],
@@ -292,11 +564,23 @@ pub struct MarkVoucherRedeemed<'info> {
     )]
     pub voucher: Account<'info, PointsVoucher>,
 
-    /// CHECK: PDA of the calling program - must be charging_session program
+    /// Voucher redeemer PDA of the charging_session program, checked in the instruction
     /// The calling program signs with this PDA to prove its identity
     pub caller_authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct UpgradeVoucher<'info> {
+    /// CHECK: May not decode with the current layout yet - owner and discriminator checked in the instruction
+    #[account(mut)]
+    pub voucher: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Marketplace {
@@ -305,6 +589,8 @@ pub struct Marketplace {
     pub total_revenue_lamports: u64,
     pub price_per_point_lamports: u64,
     pub bump: u8,
+    // Added after launch; older marketplaces are grown with upgrade_marketplace
+    pub voucher_validity_secs: i64,
 }
 
 #[account]
@@ -326,6 +612,71 @@ pub struct PointsVoucher {
     pub is_redeemed: bool,
     pub created_at: i64,
     pub bump: u8,
+    // Added after launch; older vouchers are grown with upgrade_voucher
+    pub expires_at: i64,
+}
+
+/// Mirror of charging_session's LoyaltyBenefits, decoded from get_loyalty_benefits return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct LoyaltyBenefits {
+    pub loyalty_tier: u8,
+    pub points_multiplier_bps: u16,
+    pub marketplace_discount_bps: u16,
+}
+
+#[event]
+pub struct MarketplaceInitialized {
+    pub marketplace: Pubkey,
+    pub authority: Pubkey,
+    pub price_per_point_lamports: u64,
+}
+
+#[event]
+pub struct VoucherValidityUpdated {
+    pub marketplace: Pubkey,
+    pub validity_secs: i64,
+}
+
+#[event]
+pub struct ListingCreated {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub points_amount: u64,
+    pub price_per_point: u64,
+    pub created_at: i64,
+}
+
+#[event]
+pub struct ListingPurchased {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub voucher: Pubkey,
+    pub points_amount: u64,
+    pub total_price_lamports: u64,
+}
+
+#[event]
+pub struct ListingCancelled {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+}
+
+#[event]
+pub struct VoucherIssued {
+    pub voucher: Pubkey,
+    pub buyer: Pubkey,
+    pub points_amount: u64,
+    pub price_lamports: u64,
+    pub listing: Option<Pubkey>, // None when bought from the marketplace itself
+    pub created_at: i64,
+}
+
+#[event]
+pub struct VoucherMarkedRedeemed {
+    pub voucher: Pubkey,
+    pub buyer: Pubkey,
+    pub points_amount: u64,
 }
 
 #[error_code]
@@ -340,4 +691,18 @@ pub enum ErrorCode {
     VoucherAlreadyRedeemed,
     #[msg("Unauthorized caller - only charging_session program can mark vouchers as redeemed")]
     UnauthorizedCaller,
+    #[msg("Voucher has expired")]
+    VoucherExpired,
+    #[msg("Account is not an older version of a points voucher")]
+    InvalidVoucherUpgrade,
+    #[msg("Voucher already has the current layout")]
+    VoucherUpToDate,
+    #[msg("Voucher validity must be positive")]
+    InvalidVoucherValidity,
+    #[msg("Account is not an older version of the marketplace")]
+    InvalidMarketplaceUpgrade,
+    #[msg("Marketplace already has the current layout")]
+    MarketplaceUpToDate,
+    #[msg("Could not read the buyer's loyalty benefits from charging_session")]
+    LoyaltyBenefitsUnavailable,
 }
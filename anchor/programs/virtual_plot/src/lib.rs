@@ -49,6 +49,15 @@ pub mod virtual_plot {
             ],
         )?;
 
+        emit!(PlotPurchased {
+            plot: ctx.accounts.plot.key(),
+            owner: ctx.accounts.buyer.key(),
+            plot_id,
+            latitude,
+            longitude,
+            price_lamports,
+        });
+
         msg!("Plot {} purchased at ({}, {}) for {} lamports",
              plot_id, latitude, longitude, price_lamports);
         Ok(())
@@ -87,6 +96,13 @@ pub mod virtual_plot {
         plot.charger_power_kw = charger_power_kw;
         plot.is_operational = true;
 
+        emit!(ChargerInstalled {
+            plot: plot.key(),
+            owner: plot.owner,
+            charger_power_kw,
+            installation_cost,
+        });
+
         msg!("Installed {}kW charger on plot {} for {} lamports",
              charger_power_kw, plot.plot_id, installation_cost);
         Ok(())
@@ -122,6 +138,14 @@ pub mod virtual_plot {
         let old_power = plot.charger_power_kw;
         plot.charger_power_kw = new_power_kw;
 
+        emit!(ChargerUpgraded {
+            plot: plot.key(),
+            owner: plot.owner,
+            old_power_kw: old_power,
+            new_power_kw,
+            upgrade_cost,
+        });
+
         msg!("Upgraded charger from {}kW to {}kW for {} lamports",
              old_power, new_power_kw, upgrade_cost);
         Ok(())
@@ -161,6 +185,14 @@ pub mod virtual_plot {
             .checked_add(1)
             .ok_or(ErrorCode::Overflow)?;
 
+        emit!(SessionRecorded {
+            plot: plot.key(),
+            payer: ctx.accounts.payer.key(),
+            revenue_lamports,
+            total_revenue: plot.total_revenue,
+            total_sessions: plot.total_sessions,
+        });
+
         msg!("Session recorded: {} lamports revenue (total: {})",
              revenue_lamports, plot.total_revenue);
         Ok(())
@@ -183,6 +215,13 @@ pub mod virtual_plot {
         **ctx.accounts.plot.to_account_info().try_borrow_mut_lamports()? -= amount;
         **ctx.accounts.owner.try_borrow_mut_lamports()? += amount;
 
+        emit!(RevenueWithdrawn {
+            plot: ctx.accounts.plot.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            remaining_revenue: ctx.accounts.plot.total_revenue,
+        });
+
         msg!("Withdrew {} lamports revenue", amount);
         Ok(())
     }
@@ -311,6 +350,50 @@ pub struct VirtualPlot {
     pub bump: u8,
 }
 
+#[event]
+pub struct PlotPurchased {
+    pub plot: Pubkey,
+    pub owner: Pubkey,
+    pub plot_id: u32,
+    pub latitude: i32,
+    pub longitude: i32,
+    pub price_lamports: u64,
+}
+
+#[event]
+pub struct ChargerInstalled {
+    pub plot: Pubkey,
+    pub owner: Pubkey,
+    pub charger_power_kw: u16,
+    pub installation_cost: u64,
+}
+
+#[event]
+pub struct ChargerUpgraded {
+    pub plot: Pubkey,
+    pub owner: Pubkey,
+    pub old_power_kw: u16,
+    pub new_power_kw: u16,
+    pub upgrade_cost: u64,
+}
+
+#[event]
+pub struct SessionRecorded {
+    pub plot: Pubkey,
+    pub payer: Pubkey,
+    pub revenue_lamports: u64,
+    pub total_revenue: u64,
+    pub total_sessions: u64,
+}
+
+#[event]
+pub struct RevenueWithdrawn {
+    pub plot: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub remaining_revenue: u64,
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Invalid charger power. Must be 3, 7, 11, 22, or 30 kW")]
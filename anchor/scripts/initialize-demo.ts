@@ -6,6 +6,8 @@
  * Initializes all required program accounts for the AmpereQuest demo:
  * - Marketplace (points_marketplace program)
  * - Game Engine Authority (game_engine program)
+ * - Points Token-2022 mint (charging_session program)
+ * - Protocol config: points rate, session limits, pause flag (charging_session program)
  * - User Account for demo wallet (charging_session program)
  *
  * This script is idempotent - it can be safely re-run without errors.
@@ -123,9 +125,93 @@ async function main() {
   info('(No initialization required - PDA used for CPI signing)')
 
   // ========================================
-  // 3. Initialize User Account for Demo Wallet
+  // 3. Initialize Points Mint
   // ========================================
-  header('3. Initializing User Account')
+  header('3. Initializing Points Mint')
+
+  const [pointsMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('points_mint')],
+    chargingProgram.programId
+  )
+  const [programDataPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [chargingProgram.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  )
+
+  info(`Points Mint PDA: ${pointsMintPda.toBase58()}`)
+
+  try {
+    await chargingProgram.methods
+      .initializePointsMint()
+      .accounts({
+        pointsMint: pointsMintPda,
+        admin: wallet.publicKey,
+        programData: programDataPda,
+      })
+      .rpc()
+
+    success('Points Mint initialized successfully')
+    successCount++
+  } catch (err: any) {
+    if (err.message?.includes('already in use')) {
+      warning('Points Mint already initialized (skipping)')
+      skippedCount++
+    } else {
+      error(`Failed to initialize points mint: ${err.message}`)
+      failedCount++
+    }
+  }
+
+  // ========================================
+  // 4. Initialize Protocol Config
+  // ========================================
+  header('4. Initializing Protocol Config')
+
+  const [configPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('protocol_config')],
+    chargingProgram.programId
+  )
+
+  info(`Protocol Config PDA: ${configPda.toBase58()}`)
+
+  try {
+    await chargingProgram.methods
+      .initializeProtocolConfig({
+        pointsPerKwh: new anchor.BN(10), // 1 point per 100 Wh
+        exportPointsPerKwh: new anchor.BN(20), // 1 point per 50 Wh exported to the grid
+        minDepositLamports: new anchor.BN(1_000_000),
+        maxDepositLamports: new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL),
+        maxSessionDurationSecs: 12 * 3600,
+        minSessionDurationSecs: 60, // sessions run for at least a minute
+        iceBaselineGCo2PerKwh: 800, // ~150 gCO2/km petrol car vs ~0.19 kWh/km EV
+        lowCarbonThresholdGCo2PerKwh: 100,
+        lowCarbonPointsPerKwh: new anchor.BN(5),
+        pointLifetimeSecs: 365 * 86_400, // points expire after a year
+        dailyTransferLimitPoints: new anchor.BN(1_000),
+      })
+      .accounts({
+        config: configPda,
+        admin: wallet.publicKey,
+        programData: programDataPda,
+      })
+      .rpc()
+
+    success('Protocol Config initialized successfully')
+    successCount++
+  } catch (err: any) {
+    if (err.message?.includes('already in use')) {
+      warning('Protocol Config already initialized (skipping)')
+      skippedCount++
+    } else {
+      error(`Failed to initialize protocol config: ${err.message}`)
+      failedCount++
+    }
+  }
+
+  // ========================================
+  // 5. Initialize User Account for Demo Wallet
+  // ========================================
+  header('5. Initializing User Account')
 
   const [userAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
     [Buffer.from('user'), wallet.publicKey.toBuffer()],
@@ -139,6 +225,8 @@ async function main() {
       .initializeUser()
       .accounts({
         userAccount: userAccountPda,
+        pointsMint: pointsMintPda,
+        referrerAccount: null,
         authority: wallet.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
@@ -149,7 +237,7 @@ async function main() {
     // Fetch and display user account info
     const userAccount = await chargingProgram.account.userAccount.fetch(userAccountPda)
     info(`  Total points: ${userAccount.totalPoints.toNumber()}`)
-    info(`  Available points: ${userAccount.availablePoints.toNumber()}`)
+    info(`  Legacy points awaiting migration: ${userAccount.availablePoints.toNumber()}`)
     successCount++
   } catch (err: any) {
     if (err.message?.includes('already in use')) {
@@ -159,7 +247,7 @@ async function main() {
       try {
         const userAccount = await chargingProgram.account.userAccount.fetch(userAccountPda)
         info(`  Total points: ${userAccount.totalPoints.toNumber()}`)
-        info(`  Available points: ${userAccount.availablePoints.toNumber()}`)
+        info(`  Legacy points awaiting migration: ${userAccount.availablePoints.toNumber()}`)
       } catch (fetchErr) {
         // Ignore fetch errors
       }
@@ -172,9 +260,9 @@ async function main() {
   }
 
   // ========================================
-  // 4. Virtual Plot Treasury (PDA-based, auto-initialized)
+  // 6. Virtual Plot Treasury (PDA-based, auto-initialized)
   // ========================================
-  header('4. Virtual Plot Treasury')
+  header('6. Virtual Plot Treasury')
 
   const virtualPlotProgram = anchor.workspace.VirtualPlot
   const [treasuryPda] = anchor.web3.PublicKey.findProgramAddressSync(
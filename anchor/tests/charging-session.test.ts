@@ -1,5 +1,14 @@
 import * as anchor from '@coral-xyz/anchor'
 import { Program } from '@coral-xyz/anchor'
+import {
+  createTransferCheckedInstruction,
+  ExtensionType,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getExtensionTypes,
+  getMint,
+  TOKEN_2022_PROGRAM_ID,
+} from '@solana/spl-token'
 import { ChargingSession } from '../target/types/charging_session'
 
 describe('charging_session', () => {
@@ -9,12 +18,127 @@ describe('charging_session', () => {
   const program = anchor.workspace.ChargingSession as Program<ChargingSession>
   const payer = provider.wallet as anchor.Wallet
 
+  // Charger meter whose signatures attest energy readings
+  const meter = anchor.web3.Keypair.generate()
+  // Charger device key acting for the driver during the session
+  const chargerDevice = anchor.web3.Keypair.generate()
+  // Grid operator publishing demand-response events
+  const gridOperator = anchor.web3.Keypair.generate()
+  // Oracle publishing grid carbon intensities
+  const carbonOracle = anchor.web3.Keypair.generate()
+  const gridRegion = 1
+  // Second driver: referred by the payer, later a member of the payer's fleet
+  const referee = anchor.web3.Keypair.generate()
+  const fleetId = 1
+
   let userAccountPda: anchor.web3.PublicKey
   let sessionPda: anchor.web3.PublicKey
+  let meterRegistrationPda: anchor.web3.PublicKey
+  let stationPda: anchor.web3.PublicKey
+  let programDataPda: anchor.web3.PublicKey
+  let configPda: anchor.web3.PublicKey
+  let demandResponsePda: anchor.web3.PublicKey
+  let carbonIntensityPda: anchor.web3.PublicKey
+  let seasonPda: anchor.web3.PublicKey
+  let refereeAccountPda: anchor.web3.PublicKey
+  let refereePointsAccount: anchor.web3.PublicKey
+  let pointsMintPda: anchor.web3.PublicKey
+  let userPointsAccount: anchor.web3.PublicKey
   const timestamp = Math.floor(Date.now() / 1000)
   const nonce = 0 // Using 0 for simplicity in tests
+  // Unique per run since station codes can never be reused
+  const stationCode = `CHG-${timestamp % 1_000_000}`
+
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms))
+
+  const protocolParams = {
+    pointsPerKwh: new anchor.BN(10), // 1 point per 100 Wh
+    exportPointsPerKwh: new anchor.BN(20), // 1 point per 50 Wh exported
+    minDepositLamports: new anchor.BN(100_000),
+    maxDepositLamports: new anchor.BN(10 * anchor.web3.LAMPORTS_PER_SOL),
+    maxSessionDurationSecs: 8 * 3600,
+    minSessionDurationSecs: 0,
+    iceBaselineGCo2PerKwh: 800,
+    lowCarbonThresholdGCo2PerKwh: 100,
+    lowCarbonPointsPerKwh: new anchor.BN(4), // bonus 1 point per 250 Wh on a clean grid
+    pointLifetimeSecs: 0, // points never expire
+    dailyTransferLimitPoints: new anchor.BN(20),
+  }
+
+  const stationParams = (chargerPowerKw: number, pricingPerKwh: number, connectorTypes: object[]) => ({
+    connectorTypes,
+    chargerPowerKw,
+    pricingPerKwh: new anchor.BN(pricingPerKwh),
+    energyTolerancePct: 10,
+    maxSessionDurationSecs: 4 * 3600,
+    idleTimeoutSecs: 30 * 60,
+    idleFeePerMinuteLamports: new anchor.BN(10_000),
+    idleGracePeriodSecs: 10 * 60,
+    supportsV2g: false,
+    gridRegion,
+    latitude: 51_507_400,
+    longitude: -127_800,
+  })
+
+  // Build the Ed25519 precompile instruction for a meter-signed reading
+  const signedReading = (
+    signer: anchor.web3.Keypair,
+    sequence: number,
+    cumulativeWh: number,
+    session: anchor.web3.PublicKey = sessionPda,
+    exportedWh = 0
+  ) => {
+    const message = Buffer.concat([
+      session.toBuffer(),
+      Buffer.from(new anchor.BN(sequence).toArray('le', 8)),
+      Buffer.from(new anchor.BN(cumulativeWh).toArray('le', 8)),
+      Buffer.from(new anchor.BN(exportedWh).toArray('le', 8)),
+    ])
+    return anchor.web3.Ed25519Program.createInstructionWithPrivateKey({
+      privateKey: signer.secretKey,
+      message,
+    })
+  }
+
+  const pointsBalance = async (tokenAccount: anchor.web3.PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount, 'confirmed', TOKEN_2022_PROGRAM_ID)).amount)
 
   beforeAll(async () => {
+    ;[pointsMintPda] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from('points_mint')], program.programId)
+    userPointsAccount = getAssociatedTokenAddressSync(pointsMintPda, payer.publicKey, false, TOKEN_2022_PROGRAM_ID)
+
+    ;[stationPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('station'), Buffer.from(stationCode)],
+      program.programId
+    )
+
+    ;[meterRegistrationPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('meter'), meter.publicKey.toBuffer()],
+      program.programId
+    )
+
+    ;[configPda] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from('protocol_config')], program.programId)
+
+    ;[demandResponsePda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('demand_response'), Buffer.from(new anchor.BN(gridRegion).toArray('le', 2))],
+      program.programId
+    )
+
+    ;[carbonIntensityPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('carbon_intensity'), Buffer.from(new anchor.BN(gridRegion).toArray('le', 2))],
+      program.programId
+    )
+
+    ;[seasonPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('season'), Buffer.from(new anchor.BN(1).toArray('le', 4))],
+      program.programId
+    )
+
+    ;[programDataPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+    )
+
     // Derive user account PDA
     ;[userAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from('user'), payer.publicKey.toBuffer()],
@@ -33,12 +157,269 @@ describe('charging_session', () => {
     )
   })
 
+  it('initializes the points mint', async () => {
+    try {
+      await program.methods
+        .initializePointsMint()
+        .accounts({
+          pointsMint: pointsMintPda,
+          admin: payer.publicKey,
+          programData: programDataPda,
+        })
+        .rpc()
+    } catch (error: any) {
+      // Mint may already exist from previous test runs - that's okay
+      if (!error.message?.includes('already in use')) {
+        throw error
+      }
+    }
+
+    const mintInfo = await provider.connection.getAccountInfo(pointsMintPda)
+    expect(mintInfo?.owner.equals(TOKEN_2022_PROGRAM_ID)).toBe(true)
+
+    // Points only move through the program, which keeps expiry lots in step with balances
+    const mint = await getMint(provider.connection, pointsMintPda, 'confirmed', TOKEN_2022_PROGRAM_ID)
+    expect(mint.decimals).toBe(0)
+    expect(getExtensionTypes(mint.tlvData)).toEqual(
+      expect.arrayContaining([ExtensionType.NonTransferable, ExtensionType.PermanentDelegate])
+    )
+  })
+
+  it('initializes the protocol config', async () => {
+    try {
+      await program.methods
+        .initializeProtocolConfig(protocolParams)
+        .accounts({
+          config: configPda,
+          admin: payer.publicKey,
+          programData: programDataPda,
+        })
+        .rpc()
+    } catch (error: any) {
+      // Config may already exist from previous test runs - reset it to the test values
+      if (!error.message?.includes('already in use')) {
+        throw error
+      }
+      await program.methods
+        .updateProtocolConfig(protocolParams)
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc()
+    }
+
+    const config = await program.account.protocolConfig.fetch(configPda)
+    expect(config.admin.equals(payer.publicKey)).toBe(true)
+    expect(config.pointsPerKwh.toNumber()).toBe(10)
+    expect(config.isPaused).toBe(false)
+  })
+
+  it('rotates the protocol admin with the new key co-signing', async () => {
+    const newAdmin = anchor.web3.Keypair.generate()
+
+    await program.methods
+      .setProtocolAdmin()
+      .accounts({ config: configPda, admin: payer.publicKey, newAdmin: newAdmin.publicKey })
+      .signers([newAdmin])
+      .rpc()
+    expect((await program.account.protocolConfig.fetch(configPda)).admin.equals(newAdmin.publicKey)).toBe(true)
+
+    // The old admin is locked out until the role is handed back
+    try {
+      await program.methods.setProtocolPaused(true).accounts({ config: configPda, admin: payer.publicKey }).rpc()
+      fail('Old admin should no longer control the config')
+    } catch (error: any) {
+      expect(error.message).toContain('UnauthorizedAdmin')
+    }
+
+    await program.methods
+      .setProtocolAdmin()
+      .accounts({ config: configPda, admin: newAdmin.publicKey, newAdmin: payer.publicKey })
+      .signers([newAdmin])
+      .rpc()
+    expect((await program.account.protocolConfig.fetch(configPda)).admin.equals(payer.publicKey)).toBe(true)
+  })
+
+  it('registers an authorized points caller by its authority PDA', async () => {
+    // virtual_plot would sign credit_points CPIs with its 'points_caller' PDA
+    const callerProgramId = new anchor.web3.PublicKey('Ex4pz9FX9RQUHcSdb74MzTN4hpPFAHMKfqf3RtWcVHRc')
+    const [authorizedCallerPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('authorized_caller'), callerProgramId.toBuffer()],
+      program.programId
+    )
+    const [expectedAuthority] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('points_caller')],
+      callerProgramId
+    )
+
+    if (!(await program.account.authorizedCaller.fetchNullable(authorizedCallerPda))) {
+      await program.methods
+        .registerAuthorizedCaller(callerProgramId, Buffer.from('points_caller'), new anchor.BN(1_000))
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc()
+    }
+
+    await program.methods
+      .setCallerMintCap(new anchor.BN(5_000))
+      .accounts({ authorizedCaller: authorizedCallerPda, config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const authorizedCaller = await program.account.authorizedCaller.fetch(authorizedCallerPda)
+    expect(authorizedCaller.authority.equals(expectedAuthority)).toBe(true)
+    expect(authorizedCaller.dailyMintCap.toNumber()).toBe(5_000)
+  })
+
+  it('sets the time-of-use schedule', async () => {
+    // Weekend nights earn 1.5x
+    await program.methods
+      .setTouSchedule([{ weekdayMask: 0b1000001, startHour: 22, endHour: 6, multiplierBps: 15_000 }])
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const [schedulePda] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from('tou_schedule')], program.programId)
+    const schedule = await program.account.multiplierSchedule.fetch(schedulePda)
+    expect(schedule.windows).toHaveLength(1)
+    expect(schedule.windows[0].multiplierBps).toBe(15_000)
+  })
+
+  it('registers a grid operator for the station\'s grid region', async () => {
+    const signature = await provider.connection.requestAirdrop(gridOperator.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+
+    await program.methods
+      .registerGridOperator(gridOperator.publicKey, gridRegion)
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const [gridOperatorPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('grid_operator'), gridOperator.publicKey.toBuffer()],
+      program.programId
+    )
+    expect((await program.account.gridOperator.fetch(gridOperatorPda)).gridRegion).toBe(gridRegion)
+  })
+
+  it('publishes grid carbon intensity from the admin and the carbon oracle', async () => {
+    const signature = await provider.connection.requestAirdrop(carbonOracle.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+
+    await program.methods
+      .setCarbonIntensity(gridRegion, 400)
+      .accounts({ config: configPda, authority: payer.publicKey })
+      .rpc()
+
+    // Unknown keys cannot publish until registered as the oracle
+    try {
+      await program.methods
+        .setCarbonIntensity(gridRegion, 250)
+        .accounts({ config: configPda, authority: carbonOracle.publicKey })
+        .signers([carbonOracle])
+        .rpc()
+      fail('Unregistered key should not publish carbon intensities')
+    } catch (error: any) {
+      expect(error.message).toContain('UnauthorizedCarbonOracle')
+    }
+
+    await program.methods
+      .setCarbonOracle(carbonOracle.publicKey)
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+    await program.methods
+      .setCarbonIntensity(gridRegion, 250)
+      .accounts({ config: configPda, authority: carbonOracle.publicKey })
+      .signers([carbonOracle])
+      .rpc()
+
+    const carbonIntensity = await program.account.carbonIntensity.fetch(carbonIntensityPda)
+    expect(carbonIntensity.gCo2PerKwh).toBe(250)
+    expect(carbonIntensity.updatedBy.equals(carbonOracle.publicKey)).toBe(true)
+  })
+
+  it('sets the loyalty tiers', async () => {
+    // Bronze after the first session, silver after 10 sessions and 100 kWh
+    await program.methods
+      .setLoyaltyTiers([
+        { minEnergyKwh: new anchor.BN(0), minSessions: new anchor.BN(1), pointsMultiplierBps: 11_000, marketplaceDiscountBps: 500 },
+        { minEnergyKwh: new anchor.BN(100), minSessions: new anchor.BN(10), pointsMultiplierBps: 12_500, marketplaceDiscountBps: 1_000 },
+      ])
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const config = await program.account.protocolConfig.fetch(configPda)
+    expect(config.loyaltyTiers).toHaveLength(2)
+  })
+
+  it('sets the streak rewards', async () => {
+    await program.methods
+      .setStreakRewards(
+        [
+          { days: 1, bonusPoints: new anchor.BN(5) },
+          { days: 7, bonusPoints: new anchor.BN(50) },
+        ],
+        new anchor.BN(5)
+      )
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const config = await program.account.protocolConfig.fetch(configPda)
+    expect(config.streakMilestones).toHaveLength(2)
+    expect(config.graceDayPricePoints.toNumber()).toBe(5)
+  })
+
+  it('sets the referral terms', async () => {
+    await program.methods
+      .setReferralTerms({
+        rewardPct: 50,
+        maxSessions: 3,
+        maxEnergyKwh: new anchor.BN(10),
+        maxPointsPerReferee: new anchor.BN(100),
+      })
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const config = await program.account.protocolConfig.fetch(configPda)
+    expect(config.referralTerms.rewardPct).toBe(50)
+  })
+
+  it('starts the first season', async () => {
+    // Week-long season, closed early by the admin later in the suite; prizes for the top two drivers
+    await program.methods
+      .setSeasonRewards(604_800, [new anchor.BN(30), new anchor.BN(10)])
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    await program.methods
+      .startSeason()
+      .accounts({ season: seasonPda, admin: payer.publicKey })
+      .rpc()
+
+    const config = await program.account.protocolConfig.fetch(configPda)
+    expect(config.currentSeason).toBe(1)
+    const season = await program.account.season.fetch(seasonPda)
+    expect(season.endTime.toNumber() - season.startTime.toNumber()).toBe(604_800)
+    expect(season.leaderboard).toHaveLength(0)
+
+    const [nextSeasonPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('season'), Buffer.from(new anchor.BN(2).toArray('le', 4))],
+      program.programId
+    )
+    try {
+      await program.methods
+        .startSeason()
+        .accounts({ season: nextSeasonPda, admin: payer.publicKey })
+        .rpc()
+      fail('Should have refused to start a second season')
+    } catch (error: any) {
+      expect(error.message).toContain('SeasonAlreadyRunning')
+    }
+  })
+
   it('initializes user account', async () => {
     try {
       await program.methods
         .initializeUser()
         .accounts({
           userAccount: userAccountPda,
+          pointsMint: pointsMintPda,
+          userPointsAccount,
+          referrerAccount: null,
           authority: payer.publicKey,
         })
         .rpc()
@@ -54,67 +435,560 @@ describe('charging_session', () => {
     // Don't check exact point values as they may have accumulated from previous runs
   })
 
+  it('has no legacy points to migrate for a new user', async () => {
+    try {
+      await program.methods
+        .migratePoints()
+        .accounts({
+          userAccount: userAccountPda,
+          pointsMint: pointsMintPda,
+          userPointsAccount,
+          authority: payer.publicKey,
+        })
+        .rpc()
+
+      fail('Should have failed with nothing to migrate')
+    } catch (error: any) {
+      expect(error.message).toContain('NoPointsToMigrate')
+    }
+  })
+
+  it('only upgrades accounts written with an older layout', async () => {
+    try {
+      await program.methods
+        .upgradeUserAccount()
+        .accounts({ userAccount: userAccountPda, payer: payer.publicKey })
+        .rpc()
+      fail('Should have found the account up to date')
+    } catch (error: any) {
+      expect(error.message).toContain('AccountUpToDate')
+    }
+
+    // A config account is not a user account, whatever its size
+    try {
+      await program.methods
+        .upgradeUserAccount()
+        .accounts({ userAccount: configPda, payer: payer.publicKey })
+        .rpc()
+      fail('Should have rejected a non-user account')
+    } catch (error: any) {
+      expect(error.message).toContain('InvalidAccountUpgrade')
+    }
+  })
+
+  it('registers a charger station', async () => {
+    await program.methods
+      .registerStation(stationCode, stationParams(150, 1_000_000, [{ ccs2: {} }, { type2: {} }]))
+      .accounts({
+        station: stationPda,
+        operator: payer.publicKey,
+      })
+      .rpc()
+
+    const station = await program.account.chargerStation.fetch(stationPda)
+    expect(station.operator.equals(payer.publicKey)).toBe(true)
+    expect(station.code).toBe(stationCode)
+    expect(station.connectorTypes).toHaveLength(2)
+    expect(station.chargerPowerKw).toBe(150)
+    expect(station.energyTolerancePct).toBe(10)
+    expect(station.idleTimeoutSecs).toBe(30 * 60)
+    expect(station.isActive).toBe(true)
+  })
+
+  it('updates a charger station', async () => {
+    await program.methods
+      .updateStation(stationParams(350, 1_500_000, [{ ccs2: {} }]))
+      .accounts({
+        station: stationPda,
+        operator: payer.publicKey,
+      })
+      .rpc()
+
+    const station = await program.account.chargerStation.fetch(stationPda)
+    expect(station.connectorTypes).toHaveLength(1)
+    expect(station.chargerPowerKw).toBe(350)
+    expect(station.pricingPerKwh.toNumber()).toBe(1_500_000)
+  })
+
+  it('registers a charger meter', async () => {
+    await program.methods
+      .registerMeter(meter.publicKey, stationCode, payer.publicKey)
+      .accounts({
+        meterRegistration: meterRegistrationPda,
+        config: configPda,
+        admin: payer.publicKey,
+      })
+      .rpc()
+
+    const meterRegistration = await program.account.meterRegistration.fetch(meterRegistrationPda)
+    expect(meterRegistration.meter.equals(meter.publicKey)).toBe(true)
+    expect(meterRegistration.chargerCode).toBe(stationCode)
+    expect(meterRegistration.isActive).toBe(true)
+    expect(meterRegistration.operator.equals(payer.publicKey)).toBe(true)
+  })
+
+  it('refuses sessions on a station registered under another operator\'s charger code', async () => {
+    const squatter = anchor.web3.Keypair.generate()
+    const signature = await provider.connection.requestAirdrop(squatter.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+
+    const squattedCode = `${stationCode}-B`
+    const squattedMeter = anchor.web3.Keypair.generate()
+    const [squattedStationPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('station'), Buffer.from(squattedCode)],
+      program.programId
+    )
+    const [squattedMeterPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('meter'), squattedMeter.publicKey.toBuffer()],
+      program.programId
+    )
+
+    // The real operator's meter is registered, but someone else claims the code first
+    await program.methods
+      .registerMeter(squattedMeter.publicKey, squattedCode, payer.publicKey)
+      .accounts({ meterRegistration: squattedMeterPda, config: configPda, admin: payer.publicKey })
+      .rpc()
+    await program.methods
+      .registerStation(squattedCode, stationParams(150, 5_000_000, [{ ccs2: {} }]))
+      .accounts({ station: squattedStationPda, operator: squatter.publicKey })
+      .signers([squatter])
+      .rpc()
+
+    try {
+      await program.methods
+        .startSession(new anchor.BN(timestamp), nonce + 3, new anchor.BN(0), { charge: {} })
+        .accounts({
+          escrow: null,
+          station: squattedStationPda,
+          meterRegistration: squattedMeterPda,
+          user: payer.publicKey,
+        })
+        .rpc()
+      fail('Should have refused a station with the wrong operator')
+    } catch (error: any) {
+      expect(error.message).toContain('MeterOperatorMismatch')
+    }
+  })
+
+  it('refuses to start a session while the protocol is paused', async () => {
+    await program.methods.setProtocolPaused(true).accounts({ config: configPda, admin: payer.publicKey }).rpc()
+
+    try {
+      await program.methods
+        .startSession(new anchor.BN(timestamp), nonce, new anchor.BN(0), { charge: {} })
+        .accounts({
+          session: sessionPda,
+          escrow: null,
+          station: stationPda,
+          meterRegistration: meterRegistrationPda,
+          user: payer.publicKey,
+        })
+        .rpc()
+
+      fail('Should have failed to start a session while paused')
+    } catch (error: any) {
+      expect(error.message).toContain('ProtocolPaused')
+    } finally {
+      await program.methods.setProtocolPaused(false).accounts({ config: configPda, admin: payer.publicKey }).rpc()
+    }
+  })
+
   it('starts a charging session', async () => {
     await program.methods
-      .startSession('CHG-001', 7, new anchor.BN(1_000_000), new anchor.BN(timestamp), nonce)
+      .startSession(new anchor.BN(timestamp), nonce, new anchor.BN(0), { charge: {} })
       .accounts({
         session: sessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
         user: payer.publicKey,
       })
       .rpc()
 
     const session = await program.account.chargingSession.fetch(sessionPda)
     expect(session.user.equals(payer.publicKey)).toBe(true)
-    expect(session.chargerCode).toBe('CHG-001')
-    expect(session.chargerPowerKw).toBe(7)
-    expect(session.pricingPerKwh.toNumber()).toBe(1_000_000)
+    // Power and pricing come from the station, not the driver
+    expect(session.station.equals(stationPda)).toBe(true)
+    expect(session.chargerCode).toBe(stationCode)
+    expect(session.chargerPowerKw).toBe(350)
+    expect(session.pricingPerKwh.toNumber()).toBe(1_500_000)
+    expect(session.pointsPerKwh.toNumber()).toBe(10)
+    expect(session.meter.equals(meter.publicKey)).toBe(true)
     expect(session.energyConsumedWh.toNumber()).toBe(0)
     expect(session.pointsEarned.toNumber()).toBe(0)
     expect(session.isActive).toBe(true)
   })
 
-  it('updates session with energy consumed', async () => {
-    // Simulate 5000 Wh (5 kWh) consumed
-    const energyIncrement = 5000
+  it('only upgrades sessions written with an older layout', async () => {
+    try {
+      await program.methods
+        .upgradeSession()
+        .accounts({ session: sessionPda, payer: payer.publicKey })
+        .rpc()
+      fail('Should have found the session up to date')
+    } catch (error: any) {
+      expect(error.message).toContain('AccountUpToDate')
+    }
+
+    try {
+      await program.methods
+        .upgradeSession()
+        .accounts({ session: userAccountPda, payer: payer.publicKey })
+        .rpc()
+      fail('Should have rejected a non-session account')
+    } catch (error: any) {
+      expect(error.message).toContain('InvalidAccountUpgrade')
+    }
+  })
+
+  it('takes the session start time from the clock, not the seed timestamp', async () => {
+    const futureSeed = timestamp + 30 * 24 * 3600
+    const [futureSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        payer.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(futureSeed).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce).toArray('le', 4)),
+      ],
+      program.programId
+    )
 
     await program.methods
-      .updateSession(new anchor.BN(energyIncrement))
+      .startSession(new anchor.BN(futureSeed), nonce, new anchor.BN(0), { charge: {} })
       .accounts({
-        session: sessionPda,
+        session: futureSessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
         user: payer.publicKey,
       })
       .rpc()
 
-    const session = await program.account.chargingSession.fetch(sessionPda)
-    expect(session.energyConsumedWh.toNumber()).toBe(energyIncrement)
-    // 1 point per 100 Wh, so 5000 Wh = 50 points
-    expect(session.pointsEarned.toNumber()).toBe(50)
+    const session = await program.account.chargingSession.fetch(futureSessionPda)
+    expect(session.startTime.toNumber()).toBeLessThan(futureSeed)
+    expect(Math.abs(session.startTime.toNumber() - Math.floor(Date.now() / 1000))).toBeLessThan(60)
   })
 
-  it('updates session multiple times', async () => {
-    // Add another 3000 Wh
-    const energyIncrement = 3000
+  it('updates session with energy consumed', async () => {
+    // A 350 kW charger delivers ~97 Wh per second, so wait before reporting 250 Wh
+    await sleep(3000)
 
     await program.methods
-      .updateSession(new anchor.BN(energyIncrement))
+      .updateSession(new anchor.BN(1), new anchor.BN(250), new anchor.BN(0))
       .accounts({
         session: sessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
         user: payer.publicKey,
+        authority: payer.publicKey,
       })
+      .preInstructions([signedReading(meter, 1, 250)])
       .rpc()
 
     const session = await program.account.chargingSession.fetch(sessionPda)
-    expect(session.energyConsumedWh.toNumber()).toBe(8000) // 5000 + 3000
-    expect(session.pointsEarned.toNumber()).toBe(80) // 50 + 30
+    expect(session.energyConsumedWh.toNumber()).toBe(250)
+    expect(session.lastReadingSequence.toNumber()).toBe(1)
+    // 1 point per 100 Wh, so 250 Wh = 2.5 points; the half point is kept for the next reading
+    expect(session.baseMilliPoints.toNumber()).toBe(2_500)
+    expect(session.pointsEarned.toNumber()).toBe(2)
   })
 
-  it('ends charging session and credits points to user', async () => {
+  it('updates session multiple times', async () => {
+    // 2x for the next hour in the station's region
+    const now = Math.floor(Date.now() / 1000)
     await program.methods
-      .endSession()
+      .publishDemandResponse(new anchor.BN(1), new anchor.BN(now - 60), new anchor.BN(now + 3600), 20_000)
+      .accounts({ event: demandResponsePda, authority: gridOperator.publicKey })
+      .signers([gridOperator])
+      .rpc()
+
+    const event = await program.account.demandResponseEvent.fetch(demandResponsePda)
+    expect(event.gridOperator.equals(gridOperator.publicKey)).toBe(true)
+    expect(event.gridRegion).toBe(gridRegion)
+    expect(event.multiplierBps).toBe(20_000)
+
+    await sleep(3000)
+
+    // Meter reports cumulative 500 Wh (another 250 Wh)
+    await program.methods
+      .updateSession(new anchor.BN(2), new anchor.BN(500), new anchor.BN(0))
       .accounts({
         session: sessionPda,
-        userAccount: userAccountPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
         user: payer.publicKey,
+        authority: payer.publicKey,
+      })
+      .preInstructions([signedReading(meter, 2, 500)])
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(sessionPda)
+    expect(session.energyConsumedWh.toNumber()).toBe(500) // 250 + 250
+    expect(session.pointsEarned.toNumber()).toBe(7) // 2.5 + 2.5 doubled by the demand-response event
+    expect(session.baseMilliPoints.toNumber()).toBe(5_000)
+    expect(session.touBonusMilliPoints.toNumber()).toBe(0)
+    expect(session.drBonusMilliPoints.toNumber()).toBe(2_500)
+    expect(session.anomalyCount).toBe(0)
+
+    // The event ends early; later readings in the region are back to 1x
+    await program.methods
+      .cancelDemandResponse()
+      .accounts({ event: demandResponsePda, authority: gridOperator.publicKey })
+      .signers([gridOperator])
+      .rpc()
+    expect(await provider.connection.getAccountInfo(demandResponsePda)).toBeNull()
+  })
+
+  it('grants the charger an update-only session delegate', async () => {
+    const expiresAt = Math.floor(Date.now() / 1000) + 3600
+    await program.methods
+      .grantSessionDelegate(chargerDevice.publicKey, 1, new anchor.BN(expiresAt)) // DELEGATE_SCOPE_UPDATE
+      .accounts({ session: sessionPda, user: payer.publicKey })
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(sessionPda)
+    expect(session.delegate?.equals(chargerDevice.publicKey)).toBe(true)
+    expect(session.delegateScope).toBe(1)
+  })
+
+  it('records an anomaly for physically impossible energy', async () => {
+    // 1 MWh in a few seconds is far beyond a 350 kW charger
+    // Submitted by the charger's delegate key rather than the driver
+    await program.methods
+      .updateSession(new anchor.BN(3), new anchor.BN(1_000_000), new anchor.BN(0))
+      .accounts({
+        session: sessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: payer.publicKey,
+        authority: chargerDevice.publicKey,
+      })
+      .preInstructions([signedReading(meter, 3, 1_000_000)])
+      .signers([chargerDevice])
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(sessionPda)
+    expect(session.energyConsumedWh.toNumber()).toBe(500)
+    expect(session.pointsEarned.toNumber()).toBe(7)
+    expect(session.anomalyCount).toBe(1)
+    expect(session.lastAnomalyTime).not.toBeNull()
+    expect(session.lastReadingSequence.toNumber()).toBe(3)
+    // The charger is still drawing power, so idle time restarts even though no energy was applied
+    expect(session.lastEnergyTime.toNumber()).toBe(session.lastAnomalyTime!.toNumber())
+    expect(session.lastEnergyTime.toNumber()).toBeGreaterThanOrEqual(session.lastUpdateTime.toNumber())
+  })
+
+  it('rejects readings while the session is paused', async () => {
+    await program.methods
+      .pauseSession()
+      .accounts({ session: sessionPda, user: payer.publicKey, authority: payer.publicKey })
+      .rpc()
+    expect((await program.account.chargingSession.fetch(sessionPda)).pausedAt).not.toBeNull()
+
+    try {
+      await program.methods
+        .updateSession(new anchor.BN(4), new anchor.BN(600), new anchor.BN(0))
+        .accounts({
+          session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
+          user: payer.publicKey,
+          authority: payer.publicKey,
+        })
+        .preInstructions([signedReading(meter, 4, 600)])
+        .rpc()
+
+      fail('Should have rejected a reading while paused')
+    } catch (error: any) {
+      expect(error.message).toContain('SessionPaused')
+    }
+
+    await program.methods
+      .resumeSession()
+      .accounts({ session: sessionPda, user: payer.publicKey, authority: payer.publicKey })
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(sessionPda)
+    expect(session.pausedAt).toBeNull()
+    // Well within the 10 minute grace period, so no idle fee will be charged
+    expect(session.totalPausedSecs.toNumber()).toBeLessThan(600)
+  })
+
+  it('rejects exported energy on a charge session', async () => {
+    try {
+      await program.methods
+        .updateSession(new anchor.BN(4), new anchor.BN(600), new anchor.BN(100))
+        .accounts({
+          session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
+          user: payer.publicKey,
+          authority: payer.publicKey,
+        })
+        .preInstructions([signedReading(meter, 4, 600, sessionPda, 100)])
+        .rpc()
+
+      fail('Should have rejected an export reading')
+    } catch (error: any) {
+      expect(error.message).toContain('ExportNotAllowed')
+    }
+  })
+
+  it('rejects an unsigned reading', async () => {
+    try {
+      await program.methods
+        .updateSession(new anchor.BN(4), new anchor.BN(600), new anchor.BN(0))
+        .accounts({
+          session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
+          user: payer.publicKey,
+          authority: payer.publicKey,
+        })
+        .rpc()
+
+      fail('Should have rejected an unsigned reading')
+    } catch (error: any) {
+      expect(error.message).toContain('MissingMeterSignature')
+    }
+  })
+
+  it('rejects a reading signed by another key', async () => {
+    const driverKey = anchor.web3.Keypair.generate()
+
+    try {
+      await program.methods
+        .updateSession(new anchor.BN(4), new anchor.BN(600), new anchor.BN(0))
+        .accounts({
+          session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
+          user: payer.publicKey,
+          authority: payer.publicKey,
+        })
+        .preInstructions([signedReading(driverKey, 4, 600)])
+        .rpc()
+
+      fail('Should have rejected a reading not signed by the meter')
+    } catch (error: any) {
+      expect(error.message).toContain('InvalidMeterSignature')
+    }
+  })
+
+  it('rejects a replayed reading', async () => {
+    try {
+      await program.methods
+        .updateSession(new anchor.BN(2), new anchor.BN(500), new anchor.BN(0))
+        .accounts({
+          session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
+          user: payer.publicKey,
+          authority: payer.publicKey,
+        })
+        .preInstructions([signedReading(meter, 2, 500)])
+        .rpc()
+
+      fail('Should have rejected a replayed reading')
+    } catch (error: any) {
+      expect(error.message).toContain('StaleMeterReading')
+    }
+  })
+
+  it('refuses to expire a session before its timeout', async () => {
+    try {
+      await program.methods
+        .expireSession()
+        .accounts({
+          session: sessionPda,
+          userAccount: userAccountPda,
+          escrow: null,
+          operator: null,
+          carbonIntensity: carbonIntensityPda,
+          referrerAccount: null,
+          referrerPointsAccount: null,
+          fleet: null,
+          fleetMember: null,
+          fleetPointsAccount: null,
+          season: seasonPda,
+          nextSeason: null,
+          history: null,
+          pointsMint: pointsMintPda,
+          userPointsAccount,
+          user: payer.publicKey,
+          cranker: payer.publicKey,
+        })
+        .rpc()
+
+      fail('Should have failed to expire an active session')
+    } catch (error: any) {
+      expect(error.message).toContain('SessionNotExpired')
+    }
+  })
+
+  it('does not let an update-only delegate end the session', async () => {
+    try {
+      await program.methods
+        .endSession()
+        .accounts({
+          session: sessionPda,
+          userAccount: userAccountPda,
+          escrow: null,
+          operator: null,
+          carbonIntensity: carbonIntensityPda,
+          referrerAccount: null,
+          referrerPointsAccount: null,
+          fleet: null,
+          fleetMember: null,
+          fleetPointsAccount: null,
+          season: seasonPda,
+          nextSeason: null,
+          pointsMint: pointsMintPda,
+          userPointsAccount,
+          user: payer.publicKey,
+          authority: chargerDevice.publicKey,
+        })
+        .signers([chargerDevice])
+        .rpc()
+
+      fail('Update-only delegate should not be able to end the session')
+    } catch (error: any) {
+      expect(error.message).toContain('UnauthorizedDelegate')
+    }
+  })
+
+  it('revokes the session delegate', async () => {
+    await program.methods
+      .revokeSessionDelegate()
+      .accounts({ session: sessionPda, user: payer.publicKey })
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(sessionPda)
+    expect(session.delegate).toBeNull()
+  })
+
+  it('ends charging session and mints points to user', async () => {
+    const balanceBefore = await pointsBalance(userPointsAccount)
+
+    await program.methods
+      .endSession()
+      .accounts({
+        session: sessionPda,
+        userAccount: userAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: null,
+        referrerPointsAccount: null,
+        fleet: null,
+        fleetMember: null,
+        fleetPointsAccount: null,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount,
+        user: payer.publicKey,
+        authority: payer.publicKey,
       })
       .rpc()
 
@@ -122,20 +996,71 @@ describe('charging_session', () => {
     expect(session.isActive).toBe(false)
     expect(session.endTime).not.toBeNull()
 
+    // First session on a 350 kW charger, plus night owl when it ended before 05:00 UTC
+    const nightOwl = (session.endTime!.toNumber() % 86_400) / 3600 < 5
+    const achievementBonus = 10 + 20 + (nightOwl ? 20 : 0)
+    expect(session.pointsEarned.toNumber()).toBe(7)
+    expect(session.achievementBonusPoints.toNumber()).toBe(achievementBonus)
+    // The first day of a streak reaches the 1-day milestone
+    expect(session.streakBonusPoints.toNumber()).toBe(5)
+
     const userAccount = await program.account.userAccount.fetch(userAccountPda)
-    expect(userAccount.totalPoints.toNumber()).toBe(80)
-    expect(userAccount.totalEnergyKwh.toNumber()).toBe(8) // 8000 Wh = 8 kWh
+    expect(userAccount.totalPoints.toNumber()).toBe(7 + achievementBonus + 5)
+    expect(await pointsBalance(userPointsAccount)).toBe(balanceBefore + 7 + achievementBonus + 5)
+    expect(userAccount.achievements).toBe(0b01001 | (nightOwl ? 0b10000 : 0))
+    expect(userAccount.currentStreakDays).toBe(1)
+    expect(userAccount.longestStreakDays).toBe(1)
+    expect(userAccount.totalEnergyKwh.toNumber()).toBe(0)
+    expect(userAccount.energyRemainderWh).toBe(500) // carried into the next session
     expect(userAccount.totalSessions.toNumber()).toBe(1)
+    // The leaderboard ranks session points only, without the one-off bonuses
+    expect(userAccount.seasonPoints.toNumber()).toBe(7)
+    // 500 Wh at 250 gCO2/kWh against an 800 gCO2/kWh baseline, above the low-carbon threshold
+    expect(session.gridCarbonGCo2PerKwh).toBe(250)
+    expect(session.co2AvoidedG.toNumber()).toBe(275)
+    expect(session.carbonBonusMilliPoints.toNumber()).toBe(0)
+    expect(userAccount.totalCo2AvoidedG.toNumber()).toBe(275)
+    // The first session earns no loyalty bonus but reaches bronze
+    expect(session.loyaltyBonusMilliPoints.toNumber()).toBe(0)
+    expect(userAccount.loyaltyTier).toBe(1)
+  })
+
+  it('buys a streak grace day with points', async () => {
+    const balanceBefore = await pointsBalance(userPointsAccount)
+
+    await program.methods
+      .buyGraceDay()
+      .accounts({ userAccount: userAccountPda, config: configPda, userPointsAccount, authority: payer.publicKey })
+      .rpc()
+
+    const userAccount = await program.account.userAccount.fetch(userAccountPda)
+    expect(userAccount.graceDays).toBe(1)
+    expect(await pointsBalance(userPointsAccount)).toBe(balanceBefore - 5)
+  })
+
+  it('reports loyalty benefits to other programs', async () => {
+    const benefits = await program.methods
+      .getLoyaltyBenefits()
+      .accounts({ config: configPda, userAccount: userAccountPda })
+      .view()
+
+    expect(benefits.loyaltyTier).toBe(1)
+    expect(benefits.pointsMultiplierBps).toBe(11_000)
+    expect(benefits.marketplaceDiscountBps).toBe(500)
   })
 
   it('fails to update an inactive session', async () => {
     try {
       await program.methods
-        .updateSession(new anchor.BN(1000))
+        .updateSession(new anchor.BN(4), new anchor.BN(600), new anchor.BN(0))
         .accounts({
           session: sessionPda,
+          schedule: null,
+          demandResponse: demandResponsePda,
           user: payer.publicKey,
+          authority: payer.publicKey,
         })
+        .preInstructions([signedReading(meter, 4, 600)])
         .rpc()
 
       fail('Should have failed to update inactive session')
@@ -143,4 +1068,1011 @@ describe('charging_session', () => {
       expect(error.message).toContain('SessionNotActive')
     }
   })
+
+  it('closes the finished session into the history log', async () => {
+    const [historyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('history'), payer.publicKey.toBuffer()],
+      program.programId
+    )
+    const previousRecords = (await program.account.sessionHistory.fetchNullable(historyPda))?.totalRecords.toNumber() ?? 0
+
+    await program.methods
+      .closeSession()
+      .accounts({
+        session: sessionPda,
+        history: historyPda,
+        user: payer.publicKey,
+      })
+      .rpc()
+
+    expect(await provider.connection.getAccountInfo(sessionPda)).toBeNull()
+
+    const history = await program.account.sessionHistory.fetch(historyPda)
+    expect(history.totalRecords.toNumber()).toBe(previousRecords + 1)
+    const lastRecord = history.records[(history.nextIndex + history.records.length - 1) % history.records.length]
+    expect(lastRecord.energyWh.toNumber()).toBe(500)
+    expect(lastRecord.points.toNumber()).toBe(7)
+  })
+
+  it('holds a session open for the minimum duration and pays no rewards if it delivered no energy', async () => {
+    const emptyDriver = anchor.web3.Keypair.generate()
+    const signature = await provider.connection.requestAirdrop(emptyDriver.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+
+    const [emptyDriverAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), emptyDriver.publicKey.toBuffer()],
+      program.programId
+    )
+    const emptyDriverPointsAccount = getAssociatedTokenAddressSync(
+      pointsMintPda,
+      emptyDriver.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    )
+    const [emptySessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        emptyDriver.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce).toArray('le', 4)),
+      ],
+      program.programId
+    )
+
+    await program.methods
+      .initializeUser()
+      .accounts({
+        userAccount: emptyDriverAccountPda,
+        pointsMint: pointsMintPda,
+        userPointsAccount: emptyDriverPointsAccount,
+        referrerAccount: null,
+        authority: emptyDriver.publicKey,
+      })
+      .signers([emptyDriver])
+      .rpc()
+
+    // Sessions started from here on cannot be ended in their first 2 seconds
+    await program.methods
+      .updateProtocolConfig({ ...protocolParams, minSessionDurationSecs: 2 })
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    await program.methods
+      .startSession(new anchor.BN(timestamp), nonce, new anchor.BN(0), { charge: {} })
+      .accounts({
+        session: emptySessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: emptyDriver.publicKey,
+      })
+      .signers([emptyDriver])
+      .rpc()
+
+    await program.methods
+      .updateProtocolConfig(protocolParams)
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    const endEmptySession = () =>
+      program.methods
+        .endSession()
+        .accounts({
+          session: emptySessionPda,
+          userAccount: emptyDriverAccountPda,
+          escrow: null,
+          operator: null,
+          carbonIntensity: carbonIntensityPda,
+          referrerAccount: null,
+          referrerPointsAccount: null,
+          fleet: null,
+          fleetMember: null,
+          fleetPointsAccount: null,
+          season: seasonPda,
+          nextSeason: null,
+          pointsMint: pointsMintPda,
+          userPointsAccount: emptyDriverPointsAccount,
+          user: emptyDriver.publicKey,
+          authority: emptyDriver.publicKey,
+        })
+        .signers([emptyDriver])
+        .rpc()
+
+    // Plug in and out straight away on the 350 kW charger
+    try {
+      await endEmptySession()
+      fail('Should not end a session before the minimum duration')
+    } catch (error: any) {
+      expect(error.message).toContain('SessionTooShort')
+    }
+
+    // The minimum was fixed when the session started, so lowering it since has no effect
+    await sleep(3000)
+    await endEmptySession()
+
+    // No first-session, fast-charger or streak bonus, and the session is not counted
+    const session = await program.account.chargingSession.fetch(emptySessionPda)
+    expect(session.achievementBonusPoints.toNumber()).toBe(0)
+    expect(session.streakBonusPoints.toNumber()).toBe(0)
+    const emptyDriverAccount = await program.account.userAccount.fetch(emptyDriverAccountPda)
+    expect(emptyDriverAccount.totalSessions.toNumber()).toBe(0)
+    expect(emptyDriverAccount.achievements).toBe(0)
+    expect(emptyDriverAccount.currentStreakDays).toBe(0)
+    expect(await pointsBalance(emptyDriverPointsAccount)).toBe(0)
+    const season = await program.account.season.fetch(seasonPda)
+    expect(season.leaderboard.some((entry) => entry.user.equals(emptyDriver.publicKey))).toBe(false)
+  })
+
+  it('settles a prepaid session from escrow', async () => {
+    const prepaidNonce = nonce + 1
+    const [prepaidSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        payer.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(prepaidNonce).toArray('le', 4)),
+      ],
+      program.programId
+    )
+    const [escrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('escrow'), prepaidSessionPda.toBuffer()],
+      program.programId
+    )
+
+    // 300_000 lamports at 1_500_000 lamports/kWh prepays 200 Wh
+    await program.methods
+      .startSession(new anchor.BN(timestamp), prepaidNonce, new anchor.BN(300_000), { charge: {} })
+      .accounts({
+        session: prepaidSessionPda,
+        escrow: escrowPda,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: payer.publicKey,
+      })
+      .rpc()
+
+    const escrow = await program.account.sessionEscrow.fetch(escrowPda)
+    expect(escrow.depositLamports.toNumber()).toBe(300_000)
+    expect(escrow.operator.equals(payer.publicKey)).toBe(true)
+
+    await sleep(3000)
+
+    // The meter reports more than the deposit covers, so accrual stops at 200 Wh
+    await program.methods
+      .updateSession(new anchor.BN(1), new anchor.BN(300), new anchor.BN(0))
+      .accounts({
+        session: prepaidSessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: payer.publicKey,
+        authority: payer.publicKey,
+      })
+      .preInstructions([signedReading(meter, 1, 300, prepaidSessionPda)])
+      .rpc()
+
+    let session = await program.account.chargingSession.fetch(prepaidSessionPda)
+    expect(session.energyConsumedWh.toNumber()).toBe(200)
+    expect(session.depositExhausted).toBe(true)
+
+    // The grid gets clean enough for the low-carbon bonus before the session ends
+    await program.methods
+      .setCarbonIntensity(gridRegion, 50)
+      .accounts({ config: configPda, authority: carbonOracle.publicKey })
+      .signers([carbonOracle])
+      .rpc()
+
+    await program.methods
+      .endSession()
+      .accounts({
+        session: prepaidSessionPda,
+        userAccount: userAccountPda,
+        escrow: escrowPda,
+        operator: payer.publicKey,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: null,
+        referrerPointsAccount: null,
+        fleet: null,
+        fleetMember: null,
+        fleetPointsAccount: null,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount,
+        user: payer.publicKey,
+        authority: payer.publicKey,
+      })
+      .rpc()
+
+    session = await program.account.chargingSession.fetch(prepaidSessionPda)
+    expect(session.amountPaidLamports.toNumber()).toBe(300_000)
+    // 200 Wh: 2000 base milli-points plus 800 low-carbon bonus, scaled 1.1x for bronze, and 150 g avoided
+    expect(session.carbonBonusMilliPoints.toNumber()).toBe(800)
+    expect(session.loyaltyBonusMilliPoints.toNumber()).toBe(280)
+    expect(session.pointsEarned.toNumber()).toBe(3)
+    expect(session.co2AvoidedG.toNumber()).toBe(150)
+    expect(await provider.connection.getAccountInfo(escrowPda)).toBeNull()
+  })
+
+  it('pays the referrer a share of a referred driver\'s session points', async () => {
+    const signature = await provider.connection.requestAirdrop(referee.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+
+    ;[refereeAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), referee.publicKey.toBuffer()],
+      program.programId
+    )
+    refereePointsAccount = getAssociatedTokenAddressSync(pointsMintPda, referee.publicKey, false, TOKEN_2022_PROGRAM_ID)
+    const [refereeSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        referee.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce).toArray('le', 4)),
+      ],
+      program.programId
+    )
+
+    // Points credited from here on expire after 3 seconds
+    await program.methods
+      .updateProtocolConfig({ ...protocolParams, pointLifetimeSecs: 3 })
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    await program.methods
+      .initializeUser()
+      .accounts({
+        userAccount: refereeAccountPda,
+        pointsMint: pointsMintPda,
+        userPointsAccount: refereePointsAccount,
+        referrerAccount: userAccountPda,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    const refereeAccount = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(refereeAccount.referrer?.equals(payer.publicKey)).toBe(true)
+    expect((await program.account.userAccount.fetch(userAccountPda)).referralCount).toBe(1)
+
+    // A session that delivered nothing settles without the referrer and is not counted
+    const [emptySessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        referee.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce + 5).toArray('le', 4)),
+      ],
+      program.programId
+    )
+    await program.methods
+      .startSession(new anchor.BN(timestamp), nonce + 5, new anchor.BN(0), { charge: {} })
+      .accounts({
+        session: emptySessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+    await program.methods
+      .endSession()
+      .accounts({
+        session: emptySessionPda,
+        userAccount: refereeAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: null,
+        referrerPointsAccount: null,
+        fleet: null,
+        fleetMember: null,
+        fleetPointsAccount: null,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount: refereePointsAccount,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+    expect((await program.account.userAccount.fetch(refereeAccountPda)).referralSessions).toBe(0)
+
+    await program.methods
+      .startSession(new anchor.BN(timestamp), nonce, new anchor.BN(0), { charge: {} })
+      .accounts({
+        session: refereeSessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    // 400 Wh needs about 4 seconds on the 350 kW charger
+    await sleep(5000)
+    await program.methods
+      .updateSession(new anchor.BN(1), new anchor.BN(400), new anchor.BN(0))
+      .accounts({
+        session: refereeSessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .preInstructions([signedReading(meter, 1, 400, refereeSessionPda)])
+      .signers([referee])
+      .rpc()
+
+    const referrerBalanceBefore = await pointsBalance(userPointsAccount)
+    await program.methods
+      .endSession()
+      .accounts({
+        session: refereeSessionPda,
+        userAccount: refereeAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: userAccountPda,
+        referrerPointsAccount: userPointsAccount,
+        fleet: null,
+        fleetMember: null,
+        fleetPointsAccount: null,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount: refereePointsAccount,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    // 50% of the 5 session points (4 plus the low-carbon bonus on the 50 g grid); achievement and streak bonuses are not shared
+    expect(await pointsBalance(userPointsAccount)).toBe(referrerBalanceBefore + 2)
+    const referrer = await program.account.userAccount.fetch(userAccountPda)
+    expect(referrer.referralPointsEarned.toNumber()).toBe(2)
+    const referred = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(referred.referralSessions).toBe(1)
+    expect(referred.referralPointsPaid.toNumber()).toBe(2)
+  })
+
+  it('expires lapsed points with a permissionless crank', async () => {
+    const balance = await pointsBalance(refereePointsAccount)
+    expect(balance).toBeGreaterThan(0)
+
+    // The referrer's lots are from before the lifetime was set and never expire
+    try {
+      await program.methods
+        .expirePoints()
+        .accounts({ userAccount: userAccountPda, userPointsAccount })
+        .rpc()
+      fail('Should have found no expired points')
+    } catch (error: any) {
+      expect(error.message).toContain('NoExpiredPoints')
+    }
+
+    await sleep(4000)
+    await program.methods
+      .expirePoints()
+      .accounts({ userAccount: refereeAccountPda, userPointsAccount: refereePointsAccount })
+      .rpc()
+
+    expect(await pointsBalance(refereePointsAccount)).toBe(0)
+    const referee = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(referee.expiredPoints.toNumber()).toBe(balance)
+    expect(referee.pointLots).toHaveLength(0)
+
+    await program.methods
+      .updateProtocolConfig(protocolParams)
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+  })
+
+  it('refuses token transfers that bypass the program', async () => {
+    try {
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          createTransferCheckedInstruction(
+            userPointsAccount,
+            pointsMintPda,
+            refereePointsAccount,
+            payer.publicKey,
+            1,
+            0,
+            [],
+            TOKEN_2022_PROGRAM_ID
+          )
+        )
+      )
+      fail('Should have refused to transfer non-transferable points')
+    } catch (error: any) {
+      expect(error.logs?.join('\n') ?? error.message).toContain('Transfer is disabled for this mint')
+    }
+
+    // Without side channels the tracked lots are the driver's whole balance
+    const userAccount = await program.account.userAccount.fetch(userAccountPda)
+    const tracked = userAccount.pointLots.reduce((sum, lot) => sum + lot.amount.toNumber(), 0)
+    expect(userAccount.availablePoints.toNumber()).toBe(tracked)
+    expect(await pointsBalance(userPointsAccount)).toBe(tracked)
+  })
+
+  it('transfers points to another driver within the daily limit', async () => {
+    const senderBalance = await pointsBalance(userPointsAccount)
+
+    await program.methods
+      .transferPoints(new anchor.BN(3), 'for the family car')
+      .accounts({
+        sender: payer.publicKey,
+        recipientAccount: refereeAccountPda,
+        recipientPointsAccount: refereePointsAccount,
+      })
+      .rpc()
+
+    expect(await pointsBalance(userPointsAccount)).toBe(senderBalance - 3)
+    expect(await pointsBalance(refereePointsAccount)).toBe(3)
+    const recipient = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(recipient.pointLots[0].amount.toNumber()).toBe(3)
+
+    try {
+      await program.methods
+        .transferPoints(new anchor.BN(18), null)
+        .accounts({
+          sender: payer.publicKey,
+          recipientAccount: refereeAccountPda,
+          recipientPointsAccount: refereePointsAccount,
+        })
+        .rpc()
+      fail('Should have exceeded the daily transfer limit')
+    } catch (error: any) {
+      expect(error.message).toContain('DailyTransferLimitExceeded')
+    }
+
+    const sender = await program.account.userAccount.fetch(userAccountPda)
+    expect(sender.transferDay.toNumber()).toBe(Math.floor(Date.now() / 1000 / 86_400))
+    expect(sender.transferredToday.toNumber()).toBe(3)
+  })
+
+  it('starts a new daily transfer count when the last transfer was on an earlier day', async () => {
+    // The referee has never sent points, so their counter still refers to day 0
+    let refereeAccount = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(refereeAccount.transferDay.toNumber()).toBe(0)
+
+    await program.methods
+      .transferPoints(new anchor.BN(1), null)
+      .accounts({
+        sender: referee.publicKey,
+        recipientAccount: userAccountPda,
+        recipientPointsAccount: userPointsAccount,
+      })
+      .signers([referee])
+      .rpc()
+
+    refereeAccount = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(refereeAccount.transferDay.toNumber()).toBe(Math.floor(Date.now() / 1000 / 86_400))
+    expect(refereeAccount.transferredToday.toNumber()).toBe(1)
+  })
+
+  it('refuses transfers to the sender themselves', async () => {
+    try {
+      await program.methods
+        .transferPoints(new anchor.BN(1), null)
+        .accounts({
+          sender: payer.publicKey,
+          recipientAccount: userAccountPda,
+          recipientPointsAccount: userPointsAccount,
+        })
+        .rpc()
+      fail('Should have refused a transfer to the sender')
+    } catch (error: any) {
+      expect(error.message).toContain('InvalidTransferRecipient')
+    }
+  })
+
+  it('refuses all transfers while the daily limit is 0', async () => {
+    await program.methods
+      .updateProtocolConfig({ ...protocolParams, dailyTransferLimitPoints: new anchor.BN(0) })
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+
+    try {
+      await program.methods
+        .transferPoints(new anchor.BN(1), null)
+        .accounts({
+          sender: payer.publicKey,
+          recipientAccount: refereeAccountPda,
+          recipientPointsAccount: refereePointsAccount,
+        })
+        .rpc()
+      fail('Should have refused a transfer while transfers are disabled')
+    } catch (error: any) {
+      expect(error.message).toContain('TransfersDisabled')
+    } finally {
+      await program.methods
+        .updateProtocolConfig(protocolParams)
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc()
+    }
+  })
+
+  it('pools a fleet member\'s session points and lets them withdraw within their limit', async () => {
+    const [fleetPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('fleet'), payer.publicKey.toBuffer(), Buffer.from(new anchor.BN(fleetId).toArray('le', 8))],
+      program.programId
+    )
+    const [fleetMemberPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('fleet_member'), fleetPda.toBuffer(), referee.publicKey.toBuffer()],
+      program.programId
+    )
+    const fleetPointsAccount = getAssociatedTokenAddressSync(pointsMintPda, fleetPda, true, TOKEN_2022_PROGRAM_ID)
+    const [refereeSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        referee.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce + 1).toArray('le', 4)),
+      ],
+      program.programId
+    )
+    const [leavingSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        referee.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(nonce + 2).toArray('le', 4)),
+      ],
+      program.programId
+    )
+    const poolLots = async () =>
+      (await program.account.fleet.fetch(fleetPda)).pointLots.reduce((sum, lot) => sum + lot.amount.toNumber(), 0)
+
+    await program.methods
+      .createFleet(new anchor.BN(fleetId), 'Depot North')
+      .accounts({ fleet: fleetPda, fleetPointsAccount, owner: payer.publicKey })
+      .rpc()
+
+    await program.methods
+      .addFleetMember(new anchor.BN(5))
+      .accounts({
+        fleet: fleetPda,
+        fleetMember: fleetMemberPda,
+        userAccount: refereeAccountPda,
+        member: referee.publicKey,
+        admin: payer.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    expect((await program.account.userAccount.fetch(refereeAccountPda)).fleet?.equals(fleetPda)).toBe(true)
+
+    await program.methods
+      .startSession(new anchor.BN(timestamp), nonce + 1, new anchor.BN(0), { charge: {} })
+      .accounts({
+        session: refereeSessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    await sleep(5000)
+    await program.methods
+      .updateSession(new anchor.BN(1), new anchor.BN(400), new anchor.BN(0))
+      .accounts({
+        session: refereeSessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .preInstructions([signedReading(meter, 1, 400, refereeSessionPda)])
+      .signers([referee])
+      .rpc()
+
+    const driverBalanceBefore = await pointsBalance(refereePointsAccount)
+    await program.methods
+      .endSession()
+      .accounts({
+        session: refereeSessionPda,
+        userAccount: refereeAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: userAccountPda,
+        referrerPointsAccount: userPointsAccount,
+        fleet: fleetPda,
+        fleetMember: fleetMemberPda,
+        fleetPointsAccount,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount: refereePointsAccount,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    const session = await program.account.chargingSession.fetch(refereeSessionPda)
+    const pooled =
+      session.pointsEarned.toNumber() +
+      session.achievementBonusPoints.toNumber() +
+      session.streakBonusPoints.toNumber()
+    expect(await pointsBalance(fleetPointsAccount)).toBe(pooled)
+    expect(await pointsBalance(refereePointsAccount)).toBe(driverBalanceBefore)
+
+    const fleet = await program.account.fleet.fetch(fleetPda)
+    expect(fleet.memberCount).toBe(1)
+    expect(fleet.totalSessions.toNumber()).toBe(1)
+    expect(fleet.totalEnergyWh.toNumber()).toBe(400)
+    expect(fleet.totalPointsPooled.toNumber()).toBe(pooled)
+    expect(await poolLots()).toBe(pooled)
+    // Points never expire in this config, so neither do the pool's lots
+    expect(fleet.pointLots.every((lot) => lot.expiresAt.eq(new anchor.BN('9223372036854775807')))).toBe(true)
+
+    try {
+      await program.methods
+        .expireFleetPoints()
+        .accounts({ fleet: fleetPda, fleetPointsAccount })
+        .rpc()
+      fail('Should have found no expired fleet points')
+    } catch (error: any) {
+      expect(error.message).toContain('NoExpiredPoints')
+    }
+
+    const availableBefore = (await program.account.userAccount.fetch(refereeAccountPda)).availablePoints.toNumber()
+    await program.methods
+      .withdrawFleetPoints(new anchor.BN(3))
+      .accounts({
+        fleet: fleetPda,
+        fleetMember: fleetMemberPda,
+        fleetPointsAccount,
+        memberPointsAccount: refereePointsAccount,
+        member: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    expect(await pointsBalance(refereePointsAccount)).toBe(driverBalanceBefore + 3)
+    expect(await poolLots()).toBe(pooled - 3)
+    const refereeAccount = await program.account.userAccount.fetch(refereeAccountPda)
+    expect(refereeAccount.availablePoints.toNumber()).toBe(availableBefore + 3)
+
+    // Withdrawals are bounded by the spending limit, not the daily transfer limit
+    await program.methods
+      .updateProtocolConfig({ ...protocolParams, dailyTransferLimitPoints: new anchor.BN(0) })
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+    try {
+      await program.methods
+        .withdrawFleetPoints(new anchor.BN(1))
+        .accounts({
+          fleet: fleetPda,
+          fleetMember: fleetMemberPda,
+          fleetPointsAccount,
+          memberPointsAccount: refereePointsAccount,
+          member: referee.publicKey,
+        })
+        .signers([referee])
+        .rpc()
+    } finally {
+      await program.methods
+        .updateProtocolConfig(protocolParams)
+        .accounts({ config: configPda, admin: payer.publicKey })
+        .rpc()
+    }
+    expect(await pointsBalance(refereePointsAccount)).toBe(driverBalanceBefore + 4)
+    expect((await program.account.fleetMember.fetch(fleetMemberPda)).pointsSpent.toNumber()).toBe(4)
+
+    try {
+      await program.methods
+        .withdrawFleetPoints(new anchor.BN(3))
+        .accounts({
+          fleet: fleetPda,
+          fleetMember: fleetMemberPda,
+          fleetPointsAccount,
+          memberPointsAccount: refereePointsAccount,
+          member: referee.publicKey,
+        })
+        .signers([referee])
+        .rpc()
+      fail('Should have exceeded the member spending limit')
+    } catch (error: any) {
+      expect(error.message).toContain('FleetSpendingLimitExceeded')
+    }
+
+    // A session started while in the fleet pays the pool even if the driver leaves before it ends
+    await program.methods
+      .startSession(new anchor.BN(timestamp), nonce + 2, new anchor.BN(0), { charge: {} })
+      .accounts({
+        session: leavingSessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+    expect((await program.account.chargingSession.fetch(leavingSessionPda)).fleet?.equals(fleetPda)).toBe(true)
+
+    await sleep(5000)
+    await program.methods
+      .updateSession(new anchor.BN(1), new anchor.BN(400), new anchor.BN(0))
+      .accounts({
+        session: leavingSessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .preInstructions([signedReading(meter, 1, 400, leavingSessionPda)])
+      .signers([referee])
+      .rpc()
+
+    const ownerLamportsBefore = await provider.connection.getBalance(payer.publicKey)
+    await program.methods
+      .removeFleetMember()
+      .accounts({
+        fleet: fleetPda,
+        fleetMember: fleetMemberPda,
+        userAccount: refereeAccountPda,
+        owner: payer.publicKey,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    expect((await program.account.userAccount.fetch(refereeAccountPda)).fleet).toBeNull()
+    expect(await provider.connection.getAccountInfo(fleetMemberPda)).toBeNull()
+    // The member account's rent goes to the fleet owner, not to whoever signed the removal
+    expect(await provider.connection.getBalance(payer.publicKey)).toBeGreaterThan(ownerLamportsBefore)
+
+    const poolBefore = await pointsBalance(fleetPointsAccount)
+    const driverBalanceAfterLeaving = await pointsBalance(refereePointsAccount)
+    await program.methods
+      .endSession()
+      .accounts({
+        session: leavingSessionPda,
+        userAccount: refereeAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: userAccountPda,
+        referrerPointsAccount: userPointsAccount,
+        fleet: fleetPda,
+        fleetMember: null,
+        fleetPointsAccount,
+        season: seasonPda,
+        nextSeason: null,
+        pointsMint: pointsMintPda,
+        userPointsAccount: refereePointsAccount,
+        user: referee.publicKey,
+        authority: referee.publicKey,
+      })
+      .signers([referee])
+      .rpc()
+
+    const leavingSession = await program.account.chargingSession.fetch(leavingSessionPda)
+    const leavingPooled =
+      leavingSession.pointsEarned.toNumber() +
+      leavingSession.achievementBonusPoints.toNumber() +
+      leavingSession.streakBonusPoints.toNumber()
+    expect(leavingPooled).toBeGreaterThan(0)
+    expect(await pointsBalance(fleetPointsAccount)).toBe(poolBefore + leavingPooled)
+    expect(await pointsBalance(refereePointsAccount)).toBe(driverBalanceAfterLeaving)
+    expect((await program.account.fleet.fetch(fleetPda)).totalSessions.toNumber()).toBe(2)
+  })
+
+  it('lets only the admin close a season early, then pays its winners and starts the next one', async () => {
+    let season = await program.account.season.fetch(seasonPda)
+    expect(season.leaderboard).toHaveLength(2)
+    expect(season.leaderboard[0].points.toNumber()).toBeGreaterThanOrEqual(season.leaderboard[1].points.toNumber())
+    const payerEntry = season.leaderboard.find((entry) => entry.user.equals(payer.publicKey))
+    expect(payerEntry?.points.toNumber()).toBe(
+      (await program.account.userAccount.fetch(userAccountPda)).seasonPoints.toNumber()
+    )
+
+    const [nextSeasonPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('season'), Buffer.from(new anchor.BN(2).toArray('le', 4))],
+      program.programId
+    )
+
+    try {
+      await program.methods
+        .closeSeason()
+        .accounts({ season: seasonPda, nextSeason: nextSeasonPda, cranker: referee.publicKey })
+        .signers([referee])
+        .rpc()
+      fail('Should have refused to close a running season')
+    } catch (error: any) {
+      expect(error.message).toContain('SeasonNotOver')
+    }
+
+    // The next season lasts a second, so the discharge session below ends after it is over
+    await program.methods
+      .setSeasonRewards(1, [new anchor.BN(30), new anchor.BN(10)])
+      .accounts({ config: configPda, admin: payer.publicKey })
+      .rpc()
+    await program.methods
+      .closeSeason()
+      .accounts({ season: seasonPda, nextSeason: nextSeasonPda, cranker: payer.publicKey })
+      .rpc()
+
+    season = await program.account.season.fetch(seasonPda)
+    expect(season.isClosed).toBe(true)
+    expect(season.endTime.toNumber()).toBeLessThan(season.startTime.toNumber() + 604_800)
+    expect(season.prizes.map((prize) => prize.toNumber())).toEqual([30, 10])
+    expect((await program.account.protocolConfig.fetch(configPda)).currentSeason).toBe(2)
+    expect((await program.account.season.fetch(nextSeasonPda)).leaderboard).toHaveLength(0)
+
+    const winner = season.leaderboard[0].user
+    const [winnerAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), winner.toBuffer()],
+      program.programId
+    )
+    const winnerPointsAccount = getAssociatedTokenAddressSync(pointsMintPda, winner, false, TOKEN_2022_PROGRAM_ID)
+    const balanceBefore = await pointsBalance(winnerPointsAccount)
+
+    await program.methods
+      .distributeSeasonPrize()
+      .accounts({ season: seasonPda, userAccount: winnerAccountPda, userPointsAccount: winnerPointsAccount })
+      .rpc()
+
+    expect(await pointsBalance(winnerPointsAccount)).toBe(balanceBefore + 30)
+
+    try {
+      await program.methods
+        .distributeSeasonPrize()
+        .accounts({ season: seasonPda, userAccount: winnerAccountPda, userPointsAccount: winnerPointsAccount })
+        .rpc()
+      fail('Should have refused to pay the prize twice')
+    } catch (error: any) {
+      expect(error.message).toContain('SeasonPrizeAlreadyPaid')
+    }
+  })
+
+  it('rewards energy exported in a vehicle-to-grid discharge session and rolls the season over', async () => {
+    const dischargeNonce = nonce + 4
+    const [dischargeSessionPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('session'),
+        payer.publicKey.toBuffer(),
+        Buffer.from(new anchor.BN(timestamp).toArray('le', 8)),
+        Buffer.from(new anchor.BN(dischargeNonce).toArray('le', 4)),
+      ],
+      program.programId
+    )
+    const { currentSeason } = await program.account.protocolConfig.fetch(configPda)
+    const [currentSeasonPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('season'), Buffer.from(new anchor.BN(currentSeason).toArray('le', 4))],
+      program.programId
+    )
+    const [nextSeasonPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('season'), Buffer.from(new anchor.BN(currentSeason + 1).toArray('le', 4))],
+      program.programId
+    )
+
+    await program.methods
+      .updateStation({ ...stationParams(350, 1_500_000, [{ ccs2: {} }]), supportsV2g: true })
+      .accounts({ station: stationPda, operator: payer.publicKey })
+      .rpc()
+
+    await program.methods
+      .startSession(new anchor.BN(timestamp), dischargeNonce, new anchor.BN(0), { discharge: {} })
+      .accounts({
+        session: dischargeSessionPda,
+        escrow: null,
+        station: stationPda,
+        meterRegistration: meterRegistrationPda,
+        user: payer.publicKey,
+      })
+      .rpc()
+
+    await sleep(3000)
+
+    // The meter reports 200 Wh sent to the grid and nothing drawn
+    await program.methods
+      .updateSession(new anchor.BN(1), new anchor.BN(0), new anchor.BN(200))
+      .accounts({
+        session: dischargeSessionPda,
+        schedule: null,
+        demandResponse: demandResponsePda,
+        user: payer.publicKey,
+        authority: payer.publicKey,
+      })
+      .preInstructions([signedReading(meter, 1, 0, dischargeSessionPda, 200)])
+      .rpc()
+
+    let session = await program.account.chargingSession.fetch(dischargeSessionPda)
+    expect(session.energyExportedWh.toNumber()).toBe(200)
+    expect(session.energyConsumedWh.toNumber()).toBe(0)
+    // 200 Wh at 20 points per kWh exported
+    expect(session.exportMilliPoints.toNumber()).toBe(4_000)
+    expect(session.baseMilliPoints.toNumber()).toBe(0)
+
+    const before = await program.account.userAccount.fetch(userAccountPda)
+    await program.methods
+      .endSession()
+      .accounts({
+        session: dischargeSessionPda,
+        userAccount: userAccountPda,
+        escrow: null,
+        operator: null,
+        carbonIntensity: carbonIntensityPda,
+        referrerAccount: null,
+        referrerPointsAccount: null,
+        fleet: null,
+        fleetMember: null,
+        fleetPointsAccount: null,
+        season: currentSeasonPda,
+        nextSeason: nextSeasonPda,
+        pointsMint: pointsMintPda,
+        userPointsAccount,
+        user: payer.publicKey,
+        authority: payer.publicKey,
+      })
+      .rpc()
+
+    session = await program.account.chargingSession.fetch(dischargeSessionPda)
+    expect(session.pointsEarned.toNumber()).toBeGreaterThanOrEqual(4)
+
+    // Exports are tracked separately and never count as energy charged
+    const after = await program.account.userAccount.fetch(userAccountPda)
+    expect(after.totalExportedWh.toNumber()).toBe(before.totalExportedWh.toNumber() + 200)
+    expect(after.totalEnergyKwh.toNumber()).toBe(before.totalEnergyKwh.toNumber())
+    expect(after.energyRemainderWh).toBe(before.energyRemainderWh)
+
+    // The current season was over, so ending the session closed it and counted the session
+    // towards the next one
+    expect((await program.account.season.fetch(currentSeasonPda)).isClosed).toBe(true)
+    expect((await program.account.protocolConfig.fetch(configPda)).currentSeason).toBe(currentSeason + 1)
+    const nextSeason = await program.account.season.fetch(nextSeasonPda)
+    expect(nextSeason.leaderboard).toHaveLength(1)
+    expect(nextSeason.leaderboard[0].user.equals(payer.publicKey)).toBe(true)
+    expect(nextSeason.leaderboard[0].points.toNumber()).toBe(session.pointsEarned.toNumber())
+  })
+
+  it('retires a charger station', async () => {
+    let retiredEvent: any
+    const listener = program.addEventListener('stationRetired', (event) => {
+      retiredEvent = event
+    })
+
+    await program.methods
+      .retireStation()
+      .accounts({
+        station: stationPda,
+        operator: payer.publicKey,
+      })
+      .rpc({ commitment: 'confirmed' })
+
+    await new Promise((resolve) => setTimeout(resolve, 1000))
+    await program.removeEventListener(listener)
+    expect(retiredEvent.station.equals(stationPda)).toBe(true)
+
+    const station = await program.account.chargerStation.fetch(stationPda)
+    expect(station.isActive).toBe(false)
+  })
+
+  it('fails to start a session on a retired station', async () => {
+    try {
+      await program.methods
+        .startSession(new anchor.BN(timestamp), nonce + 2, new anchor.BN(0), { charge: {} })
+        .accounts({
+          escrow: null,
+          station: stationPda,
+          meterRegistration: meterRegistrationPda,
+          user: payer.publicKey,
+        })
+        .rpc()
+
+      fail('Should have failed to start a session on a retired station')
+    } catch (error: any) {
+      expect(error.message).toContain('StationInactive')
+    }
+  })
 })
@@ -1,7 +1,10 @@
+import * as fs from 'fs'
+import * as path from 'path'
 import * as anchor from '@coral-xyz/anchor'
 import { Program } from '@coral-xyz/anchor'
 import { PointsMarketplace } from '../target/types/points_marketplace'
 import { ChargingSession } from '../target/types/charging_session'
+import { getAccount, getAssociatedTokenAddressSync, TOKEN_2022_PROGRAM_ID } from '@solana/spl-token'
 
 describe('points_marketplace', () => {
   const provider = anchor.AnchorProvider.env()
@@ -20,7 +23,22 @@ describe('points_marketplace', () => {
   let listingPda: anchor.web3.PublicKey
   let voucherPda: anchor.web3.PublicKey
   let redemptionPda: anchor.web3.PublicKey
+  let pointsMintPda: anchor.web3.PublicKey
+  let buyerPointsAccount: anchor.web3.PublicKey
   const timestamp = Math.floor(Date.now() / 1000)
+  const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms))
+
+  const voucherFor = (owner: anchor.web3.PublicKey, createdAt: number) => {
+    const [voucher] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voucher'), owner.toBuffer(), Buffer.from(new anchor.BN(createdAt).toArray('le', 8))],
+      program.programId
+    )
+    const [redemption] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('redemption'), voucher.toBuffer()],
+      chargingProgram.programId
+    )
+    return { voucher, redemption }
+  }
 
   beforeAll(async () => {
     // Derive PDAs
@@ -66,6 +84,34 @@ describe('points_marketplace', () => {
       chargingProgram.programId
     )
 
+    // Points are a Token-2022 mint owned by charging_session
+    ;[pointsMintPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('points_mint')],
+      chargingProgram.programId
+    )
+    buyerPointsAccount = getAssociatedTokenAddressSync(pointsMintPda, buyer.publicKey, false, TOKEN_2022_PROGRAM_ID)
+
+    const [programDataPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [chargingProgram.programId.toBuffer()],
+      anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+    )
+
+    try {
+      await chargingProgram.methods
+        .initializePointsMint()
+        .accounts({
+          pointsMint: pointsMintPda,
+          admin: payer.publicKey,
+          programData: programDataPda,
+        })
+        .rpc()
+    } catch (error) {
+      // Mint may already exist from the charging_session tests - that's okay
+      if (!error.message?.includes('already in use')) {
+        throw error
+      }
+    }
+
     // Airdrop to buyer
     const signature = await provider.connection.requestAirdrop(
       buyer.publicKey,
@@ -88,6 +134,7 @@ describe('points_marketplace', () => {
     expect(marketplace.totalPointsSold.toNumber()).toBe(0)
     expect(marketplace.totalRevenueLamports.toNumber()).toBe(0)
     expect(marketplace.pricePerPointLamports.toNumber()).toBe(1_000_000)
+    expect(marketplace.voucherValiditySecs.toNumber()).toBe(30 * 24 * 3600)
   })
 
   it('initializes seller user account via charging_session', async () => {
@@ -96,6 +143,7 @@ describe('points_marketplace', () => {
         .initializeUser()
         .accounts({
           userAccount: sellerAccountPda,
+          referrerAccount: null,
           authority: payer.publicKey,
         })
         .rpc()
@@ -117,6 +165,9 @@ describe('points_marketplace', () => {
         .initializeUser()
         .accounts({
           userAccount: buyerAccountPda,
+          pointsMint: pointsMintPda,
+          userPointsAccount: buyerPointsAccount,
+          referrerAccount: null,
           authority: buyer.publicKey,
         })
         .signers([buyer])
@@ -152,6 +203,7 @@ describe('points_marketplace', () => {
     expect(voucher.buyer.equals(buyer.publicKey)).toBe(true)
     expect(voucher.pointsAmount.toNumber()).toBe(pointsAmount)
     expect(voucher.isRedeemed).toBe(false)
+    expect(voucher.expiresAt.toNumber()).toBeGreaterThan(Math.floor(Date.now() / 1000))
 
     // Step 2: Redeem voucher (credits points)
     await chargingProgram.methods
@@ -160,21 +212,230 @@ describe('points_marketplace', () => {
         userAccount: buyerAccountPda,
         redemptionRecord: redemptionPda,
         voucher: voucherPda,
+        pointsMint: pointsMintPda,
+        userPointsAccount: buyerPointsAccount,
         authority: buyer.publicKey,
       })
       .signers([buyer])
       .rpc()
 
-    // Verify points were credited
+    // Verify points were minted to the buyer's points token account
     const buyerAccount = await chargingProgram.account.userAccount.fetch(buyerAccountPda)
-    expect(buyerAccount.availablePoints.toNumber()).toBe(pointsAmount)
     expect(buyerAccount.totalPoints.toNumber()).toBe(pointsAmount)
+    const buyerPoints = await getAccount(provider.connection, buyerPointsAccount, 'confirmed', TOKEN_2022_PROGRAM_ID)
+    expect(Number(buyerPoints.amount)).toBe(pointsAmount)
+
+    // Redemption is recorded on the marketplace side too
+    const redeemedVoucher = await program.account.pointsVoucher.fetch(voucherPda)
+    expect(redeemedVoucher.isRedeemed).toBe(true)
+  })
+
+  it('refuses to redeem a voucher twice', async () => {
+    try {
+      await chargingProgram.methods
+        .redeemVoucher()
+        .accounts({
+          userAccount: buyerAccountPda,
+          redemptionRecord: redemptionPda,
+          voucher: voucherPda,
+          pointsMint: pointsMintPda,
+          userPointsAccount: buyerPointsAccount,
+          authority: buyer.publicKey,
+        })
+        .signers([buyer])
+        .rpc()
+
+      fail('Should have failed to redeem the voucher twice')
+    } catch (error: any) {
+      expect(error.message).toContain('VoucherAlreadyRedeemed')
+    }
+
+    const buyerAccount = await chargingProgram.account.userAccount.fetch(buyerAccountPda)
+    expect(buyerAccount.totalPoints.toNumber()).toBe(100)
+  })
+
+  it('refuses to redeem another driver\'s voucher', async () => {
+    const { voucher, redemption } = voucherFor(payer.publicKey, timestamp + 1)
+    const { marketplaceDiscountBps } = await chargingProgram.methods
+      .getLoyaltyBenefits()
+      .accounts({ userAccount: sellerAccountPda })
+      .view()
+    const marketplaceBalanceBefore = await provider.connection.getBalance(marketplacePda)
+
+    await program.methods
+      .buyFromMarketplace(new anchor.BN(10), new anchor.BN(timestamp + 1))
+      .accounts({ marketplace: marketplacePda, voucher, buyer: payer.publicKey })
+      .rpc()
+
+    // 10 points at 0.001 SOL, half price, then the seller's loyalty tier discount
+    const expectedPrice = Math.floor((5_000_000 * (10_000 - marketplaceDiscountBps)) / 10_000)
+    expect(await provider.connection.getBalance(marketplacePda)).toBe(marketplaceBalanceBefore + expectedPrice)
+
+    try {
+      await chargingProgram.methods
+        .redeemVoucher()
+        .accounts({
+          userAccount: buyerAccountPda,
+          redemptionRecord: redemption,
+          voucher,
+          pointsMint: pointsMintPda,
+          userPointsAccount: buyerPointsAccount,
+          authority: buyer.publicKey,
+        })
+        .signers([buyer])
+        .rpc()
+
+      fail('Should have refused a voucher bought by someone else')
+    } catch (error: any) {
+      expect(error.message).toContain('UnauthorizedVoucher')
+    }
+    expect((await program.account.pointsVoucher.fetch(voucher)).isRedeemed).toBe(false)
+  })
+
+  it('refuses to redeem a marketplace account that is not a voucher', async () => {
+    const [marketplaceRedemption] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('redemption'), marketplacePda.toBuffer()],
+      chargingProgram.programId
+    )
+
+    try {
+      await chargingProgram.methods
+        .redeemVoucher()
+        .accounts({
+          userAccount: buyerAccountPda,
+          redemptionRecord: marketplaceRedemption,
+          voucher: marketplacePda,
+          pointsMint: pointsMintPda,
+          userPointsAccount: buyerPointsAccount,
+          authority: buyer.publicKey,
+        })
+        .signers([buyer])
+        .rpc()
+
+      fail('Should have refused an account with the wrong discriminator')
+    } catch (error: any) {
+      expect(error.message).toContain('InvalidVoucherData')
+    }
+  })
+
+  it('refuses to redeem an expired voucher', async () => {
+    const { voucher, redemption } = voucherFor(buyer.publicKey, timestamp + 2)
+
+    // Vouchers issued while the validity is one second lapse almost immediately
+    await program.methods
+      .setVoucherValidity(new anchor.BN(1))
+      .accounts({ marketplace: marketplacePda, authority: payer.publicKey })
+      .rpc()
+    try {
+      await program.methods
+        .buyFromMarketplace(new anchor.BN(10), new anchor.BN(timestamp + 2))
+        .accounts({ marketplace: marketplacePda, voucher, buyer: buyer.publicKey })
+        .signers([buyer])
+        .rpc()
+    } finally {
+      await program.methods
+        .setVoucherValidity(new anchor.BN(30 * 24 * 3600))
+        .accounts({ marketplace: marketplacePda, authority: payer.publicKey })
+        .rpc()
+    }
+
+    await sleep(3000)
+
+    try {
+      await chargingProgram.methods
+        .redeemVoucher()
+        .accounts({
+          userAccount: buyerAccountPda,
+          redemptionRecord: redemption,
+          voucher,
+          pointsMint: pointsMintPda,
+          userPointsAccount: buyerPointsAccount,
+          authority: buyer.publicKey,
+        })
+        .signers([buyer])
+        .rpc()
+
+      fail('Should have refused an expired voucher')
+    } catch (error: any) {
+      expect(error.message).toContain('VoucherExpired')
+    }
+
+    const buyerAccount = await chargingProgram.account.userAccount.fetch(buyerAccountPda)
+    expect(buyerAccount.totalPoints.toNumber()).toBe(100)
+  })
+
+  it('refuses to redeem an upgraded launch voucher that was already redeemed', async () => {
+    // Loaded into the validator from tests/fixtures: a 50 point voucher in the launch layout,
+    // redeemed back when only the redemption record marked it
+    const legacyBuyer = anchor.web3.Keypair.fromSecretKey(
+      Uint8Array.from(JSON.parse(fs.readFileSync(path.join(__dirname, 'fixtures/legacy-voucher-buyer.json'), 'utf8')))
+    )
+    const legacyCreatedAt = 1_700_000_000
+    const { voucher, redemption } = voucherFor(legacyBuyer.publicKey, legacyCreatedAt)
+    const [legacyBuyerAccountPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), legacyBuyer.publicKey.toBuffer()],
+      chargingProgram.programId
+    )
+    const legacyBuyerPointsAccount = getAssociatedTokenAddressSync(
+      pointsMintPda,
+      legacyBuyer.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    )
+
+    const signature = await provider.connection.requestAirdrop(legacyBuyer.publicKey, anchor.web3.LAMPORTS_PER_SOL)
+    await provider.connection.confirmTransaction(signature)
+    await chargingProgram.methods
+      .initializeUser()
+      .accounts({
+        userAccount: legacyBuyerAccountPda,
+        pointsMint: pointsMintPda,
+        userPointsAccount: legacyBuyerPointsAccount,
+        referrerAccount: null,
+        authority: legacyBuyer.publicKey,
+      })
+      .signers([legacyBuyer])
+      .rpc()
+
+    // Anyone can grow the voucher, which leaves its redeemed flag unset
+    await program.methods.upgradeVoucher().accounts({ voucher, payer: payer.publicKey }).rpc()
+    const upgraded = await program.account.pointsVoucher.fetch(voucher)
+    expect(upgraded.isRedeemed).toBe(false)
+    expect(upgraded.expiresAt.toNumber()).toBe(legacyCreatedAt + 30 * 24 * 3600)
+
+    try {
+      await chargingProgram.methods
+        .redeemVoucher()
+        .accounts({
+          userAccount: legacyBuyerAccountPda,
+          redemptionRecord: redemption,
+          voucher,
+          pointsMint: pointsMintPda,
+          userPointsAccount: legacyBuyerPointsAccount,
+          authority: legacyBuyer.publicKey,
+        })
+        .signers([legacyBuyer])
+        .rpc()
+
+      fail('Should have refused a voucher that already has a redemption record')
+    } catch (error: any) {
+      expect(error.message).toContain('VoucherAlreadyRedeemed')
+    }
+
+    const legacyBuyerAccount = await chargingProgram.account.userAccount.fetch(legacyBuyerAccountPda)
+    expect(legacyBuyerAccount.totalPoints.toNumber()).toBe(0)
   })
 
   it('creates a listing (seller sells points)', async () => {
     const pointsAmount = 50
     const pricePerPoint = 500_000 // lamports
 
+    // Indexers rely on the typed event rather than the log message
+    let listingEvent: any
+    const listener = program.addEventListener('listingCreated', (event) => {
+      listingEvent = event
+    })
+
     // Buyer has 100 points from previous test and creates a listing
     // Points stay in their account (no locking needed with voucher system)
     await program.methods
@@ -184,7 +445,12 @@ describe('points_marketplace', () => {
         seller: buyer.publicKey,
       })
       .signers([buyer])
-      .rpc()
+      .rpc({ commitment: 'confirmed' })
+
+    await new Promise((resolve) => setTimeout(resolve, 1000))
+    await program.removeEventListener(listener)
+    expect(listingEvent.listing.equals(listingPda)).toBe(true)
+    expect(listingEvent.pointsAmount.toNumber()).toBe(pointsAmount)
 
     const listing = await program.account.pointsListing.fetch(listingPda)
     expect(listing.seller.equals(buyer.publicKey)).toBe(true)
@@ -193,8 +459,8 @@ describe('points_marketplace', () => {
     expect(listing.isActive).toBe(true)
 
     // Verify points remain available (not locked in voucher system)
-    const buyerAccount = await chargingProgram.account.userAccount.fetch(buyerAccountPda)
-    expect(buyerAccount.availablePoints.toNumber()).toBe(100) // Still has all points
+    const buyerPoints = await getAccount(provider.connection, buyerPointsAccount, 'confirmed', TOKEN_2022_PROGRAM_ID)
+    expect(Number(buyerPoints.amount)).toBe(100) // Still has all points
   })
 
   it('cancels a listing', async () => {
@@ -211,7 +477,7 @@ describe('points_marketplace', () => {
     expect(listing.isActive).toBe(false)
 
     // Points stay in seller's account (no changes needed)
-    const buyerAccount = await chargingProgram.account.userAccount.fetch(buyerAccountPda)
-    expect(buyerAccount.availablePoints.toNumber()).toBe(100) // Still has all points
+    const buyerPoints = await getAccount(provider.connection, buyerPointsAccount, 'confirmed', TOKEN_2022_PROGRAM_ID)
+    expect(Number(buyerPoints.amount)).toBe(100) // Still has all points
   })
 })
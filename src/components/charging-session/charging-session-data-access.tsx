@@ -1,8 +1,9 @@
 'use client'
 
 import { getChargingSessionProgram, getChargingSessionProgramId } from '@project/anchor'
+import { getAssociatedTokenAddressSync, TOKEN_2022_PROGRAM_ID } from '@solana/spl-token'
 import { useConnection } from '@solana/wallet-adapter-react'
-import { Cluster, PublicKey } from '@solana/web3.js'
+import { Cluster, Ed25519Program, PublicKey } from '@solana/web3.js'
 import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query'
 import { useMemo } from 'react'
 import { useCluster } from '../cluster/cluster-data-access'
@@ -24,6 +25,12 @@ export function useChargingSessionProgram() {
     queryFn: () => program.account.chargingSession.all(),
   })
 
+  // Registered stations; sessions can only be started at active ones
+  const stations = useQuery({
+    queryKey: ['charging-session', 'stations', { cluster }],
+    queryFn: () => program.account.chargerStation.all(),
+  })
+
   const getProgramAccount = useQuery({
     queryKey: ['get-program-account', { cluster }],
     queryFn: () => connection.getParsedAccountInfo(programId),
@@ -33,13 +40,26 @@ export function useChargingSessionProgram() {
     program,
     programId,
     accounts,
+    stations,
     getProgramAccount,
   }
 }
 
+// A cumulative meter reading signed by the session's registered meter, relayed by the charger
+export interface SignedMeterReading {
+  readingSequence: number
+  cumulativeEnergyWh: number
+  cumulativeExportedWh: number
+  meter: PublicKey
+  signature: Uint8Array
+}
+
+const regionSeed = (gridRegion: number) => Buffer.from(new BN(gridRegion).toArray('le', 2))
+
 export function useChargingSessionProgramAccount({ account }: { account: PublicKey }) {
   const { cluster } = useCluster()
   const transactionToast = useTransactionToast()
+  const provider = useAnchorProvider()
   const { program, accounts } = useChargingSessionProgram()
   const queryClient = useQueryClient()
 
@@ -50,10 +70,42 @@ export function useChargingSessionProgramAccount({ account }: { account: PublicK
 
   const updateSession = useMutation({
     mutationKey: ['charging-session', 'update', { cluster, account }],
-    mutationFn: async (energyWhIncrement: number) => {
+    mutationFn: async (reading: SignedMeterReading) => {
+      const session = await program.account.chargingSession.fetch(account)
+      const [schedulePda] = PublicKey.findProgramAddressSync([Buffer.from('tou_schedule')], program.programId)
+      const schedule = await program.account.multiplierSchedule.fetchNullable(schedulePda)
+      const [demandResponsePda] = PublicKey.findProgramAddressSync(
+        [Buffer.from('demand_response'), regionSeed(session.gridRegion)],
+        program.programId
+      )
+
+      // The program checks this meter signature in the instruction right before update_session
+      const message = Buffer.concat([
+        account.toBuffer(),
+        Buffer.from(new BN(reading.readingSequence).toArray('le', 8)),
+        Buffer.from(new BN(reading.cumulativeEnergyWh).toArray('le', 8)),
+        Buffer.from(new BN(reading.cumulativeExportedWh).toArray('le', 8)),
+      ])
+      const meterInstruction = Ed25519Program.createInstructionWithPublicKey({
+        publicKey: reading.meter.toBytes(),
+        message,
+        signature: reading.signature,
+      })
+
       return program.methods
-        .updateSession(new BN(energyWhIncrement))
-        .accounts({ session: account })
+        .updateSession(
+          new BN(reading.readingSequence),
+          new BN(reading.cumulativeEnergyWh),
+          new BN(reading.cumulativeExportedWh)
+        )
+        .accounts({
+          session: account,
+          schedule: schedule ? schedulePda : null,
+          demandResponse: demandResponsePda,
+          user: session.user,
+          authority: provider.wallet.publicKey,
+        })
+        .preInstructions([meterInstruction])
         .rpc()
     },
     onSuccess: (signature) => {
@@ -73,11 +125,50 @@ export function useChargingSessionProgramAccount({ account }: { account: PublicK
   const endSession = useMutation({
     mutationKey: ['charging-session', 'end', { cluster, account }],
     mutationFn: async (userAccountPda: PublicKey) => {
+      const session = await program.account.chargingSession.fetch(account)
+      const userAccount = await program.account.userAccount.fetch(userAccountPda)
+      const [configPda] = PublicKey.findProgramAddressSync([Buffer.from('protocol_config')], program.programId)
+      const config = await program.account.protocolConfig.fetch(configPda)
+      const [pointsMintPda] = PublicKey.findProgramAddressSync([Buffer.from('points_mint')], program.programId)
+      const pointsAccount = (owner: PublicKey) =>
+        getAssociatedTokenAddressSync(pointsMintPda, owner, true, TOKEN_2022_PROGRAM_ID)
+      const pda = (...seeds: Buffer[]) => PublicKey.findProgramAddressSync(seeds, program.programId)[0]
+
+      // Prepaid sessions settle their escrow with the station operator
+      const escrowPda = pda(Buffer.from('escrow'), account.toBuffer())
+      const escrow = session.depositLamports.gtn(0) ? await program.account.sessionEscrow.fetch(escrowPda) : null
+
+      // The current season is required once one is running; if it is over, ending the session starts the next
+      const seasonSeed = (id: number) => Buffer.from(new BN(id).toArray('le', 4))
+      const seasonPda = config.currentSeason > 0 ? pda(Buffer.from('season'), seasonSeed(config.currentSeason)) : null
+      const season = seasonPda ? await program.account.season.fetch(seasonPda) : null
+      const seasonOver = season !== null && season.endTime.toNumber() <= Math.floor(Date.now() / 1000)
+
+      const referrer = userAccount.referrer
+      const fleet = session.fleet
+
       return program.methods
         .endSession()
         .accounts({
           session: account,
           userAccount: userAccountPda,
+          escrow: escrow ? escrowPda : null,
+          operator: escrow ? escrow.operator : null,
+          carbonIntensity: pda(Buffer.from('carbon_intensity'), regionSeed(session.gridRegion)),
+          referrerAccount: referrer ? pda(Buffer.from('user'), referrer.toBuffer()) : null,
+          referrerPointsAccount: referrer ? pointsAccount(referrer) : null,
+          fleet,
+          fleetMember:
+            fleet && userAccount.fleet?.equals(fleet)
+              ? pda(Buffer.from('fleet_member'), fleet.toBuffer(), session.user.toBuffer())
+              : null,
+          fleetPointsAccount: fleet ? pointsAccount(fleet) : null,
+          season: seasonPda,
+          nextSeason: seasonOver ? pda(Buffer.from('season'), seasonSeed(config.currentSeason + 1)) : null,
+          pointsMint: pointsMintPda,
+          userPointsAccount: pointsAccount(session.user),
+          user: session.user,
+          authority: provider.wallet.publicKey,
         })
         .rpc()
     },
@@ -151,15 +242,7 @@ export function useUserAccount({ owner }: { owner: PublicKey }) {
 
   const startSession = useMutation({
     mutationKey: ['charging-session', 'start', { cluster }],
-    mutationFn: async ({
-      chargerCode,
-      chargerPowerKw,
-      pricingPerKwh,
-    }: {
-      chargerCode: string
-      chargerPowerKw: number
-      pricingPerKwh: number
-    }) => {
+    mutationFn: async ({ station, depositLamports = 0 }: { station: PublicKey; depositLamports?: number }) => {
       const timestamp = Math.floor(Date.now() / 1000)
       const nonce = Math.floor(Math.random() * 1000000) // Random nonce to avoid collisions
       const [sessionPda] = PublicKey.findProgramAddressSync(
@@ -172,9 +255,26 @@ export function useUserAccount({ owner }: { owner: PublicKey }) {
         program.programId
       )
 
+      // The station's meter attests every reading, so the session is bound to its registration
+      const { code } = await program.account.chargerStation.fetch(station)
+      const meterRegistration = (await program.account.meterRegistration.all()).find(
+        (registration) => registration.account.chargerCode === code && registration.account.isActive
+      )
+      if (!meterRegistration) {
+        throw new Error(`No active meter is registered for station ${code}`)
+      }
+      const [escrowPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from('escrow'), sessionPda.toBuffer()],
+        program.programId
+      )
+
       return program.methods
-        .startSession(chargerCode, chargerPowerKw, new BN(pricingPerKwh), new BN(timestamp), nonce)
+        .startSession(new BN(timestamp), nonce, new BN(depositLamports), { charge: {} })
         .accounts({
+          session: sessionPda,
+          escrow: depositLamports > 0 ? escrowPda : null,
+          station,
+          meterRegistration: meterRegistration.publicKey,
           user: owner,
         })
         .rpc()
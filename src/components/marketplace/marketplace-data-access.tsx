@@ -173,11 +173,21 @@ export function useMarketplaceUser({ owner }: { owner: PublicKey }) {
         ],
         program.programId
       )
+      // The price is discounted by the buyer's loyalty tier, read from charging_session
+      const [protocolConfigPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from('protocol_config')],
+        chargingSessionProgramId
+      )
 
       return program.methods
         .buyFromMarketplace(new BN(pointsAmount), new BN(timestamp))
         .accounts({
+          marketplace: marketplacePda,
+          voucher: voucherPda,
           buyer: owner,
+          protocolConfig: protocolConfigPda,
+          userAccount: userAccountPda,
+          chargingProgram: chargingSessionProgramId,
         })
         .rpc()
     },
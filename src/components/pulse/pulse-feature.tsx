@@ -5,7 +5,7 @@ import { motion, AnimatePresence } from 'framer-motion'
 import { Activity, TrendingUp, Users, Zap, Wallet, Plus, Map, List } from 'lucide-react'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
 import { Button } from '@/components/ui/button'
-import { ChargingSimulator, type ChargingSession, getAllChargePoints } from '@/lib/simulation'
+import { ChargingSimulator, type ChargingSession } from '@/lib/simulation'
 import { ChargingSessionCard } from './charging-session-card'
 import { UserStatsCard } from './user-stats-card'
 import { AchievementBadges } from './achievement-badges'
@@ -17,6 +17,7 @@ import { PublicKey } from '@solana/web3.js'
 import { useState as useStateHook } from 'react'
 import { Alert, AlertDescription } from '@/components/ui/alert'
 import dynamic from 'next/dynamic'
+import { toast } from 'sonner'
 
 // Dynamically import Leaflet-based map to avoid SSR issues
 const ChargingMap = dynamic(() => import('./charging-map').then((mod) => mod.ChargingMap), {
@@ -25,7 +26,7 @@ const ChargingMap = dynamic(() => import('./charging-map').then((mod) => mod.Cha
 
 export function PulseFeature() {
   const { publicKey, connected } = useWallet()
-  const { accounts } = useChargingSessionProgram()
+  const { accounts, stations } = useChargingSessionProgram()
   // Use placeholder key when wallet not connected (hook must always be called)
   const placeholderKey = new PublicKey('11111111111111111111111111111111')
   const userHooks = useUserAccount({ owner: publicKey || placeholderKey })
@@ -127,15 +128,15 @@ export function PulseFeature() {
   const handleStartSession = async () => {
     if (!userHooks) return
 
-    // Get a random charge point
-    const chargePoints = getAllChargePoints()
-    const randomPoint = chargePoints[Math.floor(Math.random() * chargePoints.length)]
+    // Sessions start at a registered station; power and pricing come from its on-chain settings
+    const activeStations = (stations.data ?? []).filter((station) => station.account.isActive)
+    if (activeStations.length === 0) {
+      toast.error('No active charging stations are registered')
+      return
+    }
+    const randomStation = activeStations[Math.floor(Math.random() * activeStations.length)]
 
-    await userHooks.startSession.mutateAsync({
-      chargerCode: randomPoint.code,
-      chargerPowerKw: randomPoint.connectors[0].power_kw,
-      pricingPerKwh: randomPoint.pricing.energy_based.rate * 1_000_000, // Convert to lamports
-    })
+    await userHooks.startSession.mutateAsync({ station: randomStation.publicKey })
 
     setShowStartSession(false)
   }